@@ -1,21 +1,35 @@
 use crossterm::{
+    cursor,
     event::{
-        self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyEvent, KeyModifiers,
+        self, DisableFocusChange, DisableMouseCapture, EnableFocusChange, EnableMouseCapture,
+        Event, KeyCode, KeyEvent, KeyModifiers,
     },
     execute,
     terminal::{EnterAlternateScreen, LeaveAlternateScreen, disable_raw_mode, enable_raw_mode},
 };
+use rand::{
+    Rng, SeedableRng,
+    distributions::{Distribution, WeightedIndex},
+    rngs::StdRng,
+    seq::SliceRandom,
+};
 use ratatui::{
     Terminal,
     backend::CrosstermBackend,
+    buffer::Buffer,
     layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     text::{Line, Span},
-    widgets::{Block, Borders, Gauge, Paragraph, Wrap},
+    widgets::{Block, Borders, Clear, Gauge, Paragraph, Widget, Wrap},
 };
+use std::collections::BTreeMap;
 use std::error::Error;
-use std::io;
-use std::time::{Duration, Instant};
+use std::fs;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 // Basic position struct for any entity (player, bullet, enemy)
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -24,462 +38,8567 @@ struct Pos {
     y: u16,
 }
 
-// Game configuration parameters
-struct GameConfig {
-    tick_ms: u64,
-    initial_enemy_rows: usize,
-    initial_enemy_cols: usize,
-    enemy_move_every_ticks: u64,
-    enemy_speedup_every_kills: usize,
+// Lowest accepted tick interval; anything faster is unplayable
+const MIN_TICK_MS: u64 = 10;
+
+// Event log: number of entries kept and ticks each stays visible
+const EVENT_LOG_LEN: usize = 6;
+const EVENT_TTL_TICKS: u8 = 60;
+
+// Ticks the heat gauge flashes after a shot blocked by an overheated gun
+const BLOCKED_SHOT_TICKS: u8 = 3;
+
+// Top of the weapon heat gauge; reaching it overheats the gun
+const MAX_HEAT: u32 = 100;
+
+// Charged beam: a second shot press within this many ticks starts charging, and the
+// charge is released once presses stop arriving for longer than the release gap.
+// Terminals don't report key releases, so a held key is seen as repeated presses.
+const BEAM_DOUBLE_PRESS_TICKS: u64 = 6;
+const BEAM_RELEASE_TICKS: u64 = 2;
+// Ticks a fired beam stays on screen, and the damage it does to a boss
+const BEAM_FLASH_TICKS: u8 = 3;
+const BEAM_BOSS_DAMAGE: u32 = 5;
+
+// Time attack: a wave cleared faster than this earns half the seconds it saved as extra time
+const TIME_ATTACK_PAR_SECS: u64 = 30;
+
+// Lives shown as ship icons in the header; more are shown as a count
+const MAX_LIFE_ICONS: usize = 5;
+
+// Sparks where bullets cancel out: how many can show at once and for how long (a single frame)
+const SPARK_CAP: usize = 16;
+const SPARK_TTL_TICKS: u8 = 1;
+
+// Victory fireworks: particles on screen at once, ticks between launches,
+// a rocket's longest fuse, and how long burst sparks last
+const FIREWORK_CAP: usize = 64;
+const FIREWORK_LAUNCH_EVERY: u64 = 4;
+const FIREWORK_FUSE_TICKS: u8 = 8;
+const FIREWORK_SPARK_TICKS: u8 = 6;
+
+const FIREWORK_COLORS: [Color; 6] = [
+    Color::LightRed,
+    Color::LightYellow,
+    Color::LightGreen,
+    Color::LightCyan,
+    Color::LightMagenta,
+    Color::White,
+];
+
+// Chained kills per step of the combo multiplier, and its ceiling
+const COMBO_KILLS_PER_STEP: usize = 5;
+const COMBO_MAX_MULTIPLIER: usize = 4;
+
+// Consecutive transient terminal errors tolerated before giving up
+const MAX_IO_RETRIES: u32 = 50;
+
+// Repeats of the same screen-changing action within this window are ignored
+const ACTION_DEBOUNCE: Duration = Duration::from_millis(250);
+
+// Fastest enemy fire cadence: endless mode escalates to it, sudden death opens with it
+const MIN_ENDLESS_FIRE_TICKS: u64 = 3;
+
+// Longest a score submission may wait on each network step
+const SUBMIT_TIMEOUT: Duration = Duration::from_secs(2);
+
+// Fastest formation step a panicked swarm can reach
+const MIN_PANIC_MOVE_TICKS: u64 = 2;
+
+// Minimum time between redraws (~60 fps)
+const FRAME_TIME: Duration = Duration::from_millis(16);
+
+// Most enemies diving at once, and how far a dive swings sideways (cells)
+const MAX_DIVERS: usize = 2;
+const DIVE_SWING: f32 = 4.0;
+
+// Boss body, and the kill-point multiple its destruction is worth
+const BOSS_SHAPE: [&str; 2] = ["/=###=\\", " \\v-v/ "];
+const BOSS_WIDTH: u16 = 7;
+const BOSS_KILL_MULTIPLIER: usize = 50;
+
+// Hits a shield cell takes before it crumbles
+const SHIELD_HP: u8 = 3;
+
+// Bunker layout, bottom row flush with the row two above the player
+const SHIELD_SHAPE: [&str; 2] = [" ### ", "## ##"];
+
+// Ticks between steps of the mystery UFO, and the bonuses it may be worth
+const UFO_MOVE_EVERY_TICKS: u64 = 2;
+const UFO_BONUSES: [usize; 6] = [50, 100, 150, 200, 250, 300];
+
+// Ticks between each cell a dropped power-up falls
+const POWERUP_FALL_EVERY_TICKS: u64 = 2;
+
+// Extra cells per movement action the ships can earn on top of `player_step`
+const MAX_SPEED_UPGRADES: u16 = 2;
+
+// Ticks between each cell an asteroid drifts down
+const ASTEROID_FALL_EVERY_TICKS: u64 = 3;
+
+// Board width at which the scaled enemy step is one cell
+const ENEMY_STEP_REFERENCE_WIDTH: u16 = 80;
+
+// A player shot; `tier` is the weapon level it was fired at, `dx` its
+// sideways drift per tick (0 straight up, -1/1 for angled shots), `hit`
+// whether it has destroyed anything yet, `pierce` how many more enemies
+// it can destroy when bullets stop on hit and `owner` the ship that fired it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Bullet {
+    pos: Pos,
+    tier: usize,
+    dx: i8,
+    hit: bool,
+    pierce: u8,
+    owner: usize,
 }
 
-// Holds all dynamic game state
-struct GameState {
-    width: u16,
-    height: u16,
-    player: Pos,
-    bullets: Vec<Pos>,
-    enemies: Vec<Pos>,
+// Ship colors by player
+const SHIP_COLORS: [Color; 2] = [Color::Cyan, Color::LightGreen];
+
+// A player's ship; co-op runs have two side by side on the bottom row.
+// With shared lives every ship mirrors the team's pool.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Ship {
+    pos: Pos,
+    // Points scored by this ship's own shots
     score: usize,
-    kills: usize,
-    tick_count: u64,
-    enemy_tick_acc: u64,
-    enemy_move_every_ticks: u64,
-    enemy_direction: i8,
-    game_over: bool,
-    victory: bool,
-    spawn_rows: usize,
-    spawn_cols: usize,
-    level: usize,
+    lives: usize,
+    // Ticks left of the ship's invulnerability after losing a life
+    invulnerable: u64,
+    // Weapon heat out of MAX_HEAT, and whether the gun is locked until it cools
+    heat: u32,
+    overheated: bool,
 }
 
-impl GameState {
-    // Initialize new game
-    fn new(width: u16, height: u16, cfg: &GameConfig) -> Self {
-        let player = Pos {
-            x: width / 2,
-            y: height - 3,
-        };
-        let mut gs = GameState {
-            width,
-            height,
-            player,
-            bullets: Vec::new(),
-            enemies: Vec::new(),
-            score: 0,
-            kills: 0,
-            tick_count: 0,
-            enemy_tick_acc: 0,
-            enemy_move_every_ticks: cfg.enemy_move_every_ticks,
-            enemy_direction: 1,
-            game_over: false,
-            victory: false,
-            spawn_rows: cfg.initial_enemy_rows,
-            spawn_cols: cfg.initial_enemy_cols,
-            level: 1,
-        };
-        gs.spawn_enemies();
-        gs
+impl Ship {
+    // Fresh ships for a run, spread evenly along the bottom row
+    fn fleet(width: u16, height: u16, cfg: &GameConfig) -> Vec<Ship> {
+        let count = if cfg.coop { 2 } else { 1 };
+        (0..count)
+            .map(|i| Ship {
+                pos: Pos {
+                    x: ship_home_x(width, i, count),
+                    y: height - 3,
+                },
+                score: 0,
+                lives: cfg.starting_lives.max(1),
+                invulnerable: 0,
+                heat: 0,
+                overheated: false,
+            })
+            .collect()
     }
 
-    // Generate a grid of enemies at the top
-    fn spawn_enemies(&mut self) {
-        self.enemies.clear();
-        let left_margin = 2;
-        let right_margin = 2;
-        let usable_w = self.width.saturating_sub(left_margin + right_margin);
-        let cols = self.spawn_cols as u16;
-        let spacing_x = (usable_w / (cols + 1)).max(1);
+    // A ship out of lives has left the game
+    fn in_play(&self) -> bool {
+        self.lives > 0
+    }
+}
 
-        for row in 0..self.spawn_rows as u16 {
-            for col in 0..cols {
-                let x = left_margin + spacing_x * (col + 1);
-                let y = 2 + row * 2;
-                if x < self.width - 1 && y < self.height - 2 {
-                    self.enemies.push(Pos { x, y });
-                }
-            }
+// Column ship `i` of `count` starts and respawns in: the center for one ship
+fn ship_home_x(width: u16, i: usize, count: usize) -> u16 {
+    (width as usize * (i + 1) / (count + 1)) as u16
+}
+
+// Invader species: the top row are squids, the next two crabs, the rest octopuses.
+// The higher up a kind sits, the more it is worth.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum EnemyKind {
+    Squid,
+    Crab,
+    Octopus,
+}
+
+impl EnemyKind {
+    // Kind for a formation row, counted from the top
+    fn for_row(row: u16) -> EnemyKind {
+        match row {
+            0 => EnemyKind::Squid,
+            1 | 2 => EnemyKind::Crab,
+            _ => EnemyKind::Octopus,
         }
     }
 
-    // Reset state for restart
-    fn reset(&mut self, cfg: &GameConfig) {
-        self.player = Pos {
-            x: self.width / 2,
-            y: self.height - 3,
-        };
-        self.bullets.clear();
-        self.enemies.clear();
-        self.score = 0;
-        self.kills = 0;
-        self.tick_count = 0;
-        self.enemy_tick_acc = 0;
-        self.enemy_move_every_ticks = cfg.enemy_move_every_ticks;
-        self.enemy_direction = 1;
-        self.game_over = false;
-        self.victory = false;
-        self.spawn_rows = cfg.initial_enemy_rows;
-        self.spawn_cols = cfg.initial_enemy_cols;
-        self.level = 1;
-        self.spawn_enemies();
+    fn glyph(self) -> char {
+        match self {
+            EnemyKind::Squid => '%',
+            EnemyKind::Crab => '#',
+            EnemyKind::Octopus => '&',
+        }
     }
 
-    // Update all entities and handle game logic each tick
-    fn tick(&mut self, _cfg: &GameConfig) {
-        if self.game_over || self.victory {
-            return;
+    fn color(self) -> Color {
+        match self {
+            EnemyKind::Squid => Color::LightCyan,
+            EnemyKind::Crab => Color::LightGreen,
+            EnemyKind::Octopus => Color::Yellow,
         }
+    }
 
-        self.tick_count += 1;
-        self.enemy_tick_acc += 1;
-
-        // Move bullets up
-        for b in self.bullets.iter_mut() {
-            if b.y > 0 {
-                b.y -= 1;
-            }
+    // Multiple of the base kill points a kill is worth (10/20/30 by default)
+    fn points(self) -> usize {
+        match self {
+            EnemyKind::Squid => 3,
+            EnemyKind::Crab => 2,
+            EnemyKind::Octopus => 1,
         }
-        self.bullets.retain(|b| b.y > 0);
+    }
+}
 
-        // Detect bullet-enemy collisions
-        let mut to_remove = Vec::new();
-        for b in &self.bullets {
-            if let Some(ei) = self.enemies.iter().position(|e| e.x == b.x && e.y == b.y) {
-                to_remove.push(ei);
-                self.score += 10;
-                self.kills += 1;
-            }
-        }
-        to_remove.sort_unstable();
-        to_remove.dedup();
-        for idx in to_remove.iter().rev() {
-            if *idx < self.enemies.len() {
-                self.enemies.remove(*idx);
-            }
+// Shape a regular wave is laid out in, within its rows x cols grid
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Formation {
+    Grid,
+    // Outer columns high, the center low
+    V,
+    Diamond,
+    // Every other cell, alternating per row
+    Checker,
+}
+
+impl Formation {
+    fn label(self) -> &'static str {
+        match self {
+            Formation::Grid => "Grid",
+            Formation::V => "V",
+            Formation::Diamond => "Diamond",
+            Formation::Checker => "Checker",
         }
+    }
 
-        // Level up when all enemies are gone
-        if self.enemies.is_empty() {
-            self.level += 1;
-            if self.level % 2 == 0 {
-                self.spawn_rows = (self.spawn_rows + 1).min(6);
-            } else {
-                self.spawn_cols = (self.spawn_cols + 1).min(12);
+    // Whether the cell at `row`, `col` of a `rows` x `cols` grid is part of the shape
+    fn contains(self, row: u16, col: u16, rows: u16, cols: u16) -> bool {
+        // Distance of a cell's center from the middle, as a fraction of the half-extent
+        let off = |i: u16, n: u16| (2.0 * i as f32 - (n as f32 - 1.0)).abs() / n.max(1) as f32;
+        match self {
+            Formation::Grid => true,
+            Formation::Checker => (row + col).is_multiple_of(2),
+            // Two cells thick, sloping from the top corners down to the bottom center
+            Formation::V => {
+                let depth = ((1.0 - off(col, cols)) * rows.saturating_sub(1) as f32).round() as u16;
+                row <= depth && row + 1 >= depth
             }
-            self.enemy_move_every_ticks = self.enemy_move_every_ticks.saturating_sub(1).max(1);
-            self.spawn_enemies();
+            Formation::Diamond => off(col, cols) + off(row, rows) <= 1.0,
         }
+    }
+}
 
-        // Move enemies horizontally and down
-        if self.enemy_tick_acc >= self.enemy_move_every_ticks {
-            self.enemy_tick_acc = 0;
-            let shift = self.enemy_direction as i16;
-            let hit_side = self
-                .enemies
-                .iter()
-                .any(|e| e.x as i16 + shift <= 1 || e.x as i16 + shift >= (self.width as i16 - 2));
+// A formation enemy, possibly away on a dive
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Enemy {
+    pos: Pos,
+    kind: EnemyKind,
+    dive: Option<Dive>,
+}
 
-            if hit_side {
-                // move down and reverse direction
-                for e in &mut self.enemies {
-                    e.y += 1;
-                }
-                self.enemy_direction *= -1;
-            } else {
-                for e in &mut self.enemies {
-                    e.x = (e.x as i16 + shift) as u16;
-                }
-            }
+impl Enemy {
+    fn new(pos: Pos, kind: EnemyKind) -> Self {
+        Enemy {
+            pos,
+            kind,
+            dive: None,
         }
+    }
 
-        // Check if enemies reached bottom
-        if self.enemies.iter().any(|e| e.y >= self.player.y) {
-            self.game_over = true;
-        }
+    // Its place in the formation, which keeps moving with the rest while it dives
+    fn slot(&self) -> Pos {
+        self.dive.map_or(self.pos, |d| d.home)
     }
 
-    // Player shooting
-    fn shoot(&mut self) {
-        if self.bullets.len() < 3 {
-            self.bullets.push(Pos {
-                x: self.player.x,
-                y: self.player.y.saturating_sub(1),
-            });
+    fn slot_mut(&mut self) -> &mut Pos {
+        match &mut self.dive {
+            Some(dive) => &mut dive.home,
+            None => &mut self.pos,
         }
     }
+}
+
+// A dive-bomb run: `t` ticks into a `len`-tick loop from the formation slot
+// `home` down to the player's row, swinging toward column `target_x` on the way
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Dive {
+    home: Pos,
+    target_x: u16,
+    t: u16,
+    len: u16,
+}
 
-    // Player movement
-    fn move_player_left(&mut self) {
-        if self.player.x > 1 {
-            self.player.x -= 1;
+impl Dive {
+    // Position along the loop: out and back down a half sine, with a sideways
+    // swing so the way back doesn't retrace the way down
+    fn pos(&self, floor: u16, width: u16) -> Pos {
+        let u = self.t as f32 / self.len.max(1) as f32;
+        let arc = (std::f32::consts::PI * u).sin();
+        let swing = (2.0 * std::f32::consts::PI * u).sin() * DIVE_SWING;
+        let depth = floor.saturating_sub(self.home.y) as f32;
+        let dx = self.target_x as f32 - self.home.x as f32;
+        let x = self.home.x as f32 + dx * arc + swing;
+        Pos {
+            x: (x.round().max(1.0) as u16).min(width.saturating_sub(2)),
+            y: self.home.y + (depth * arc).round() as u16,
         }
     }
-    fn move_player_right(&mut self) {
-        if self.player.x < self.width.saturating_sub(2) {
-            self.player.x += 1;
-        }
+}
+
+// A boss: one big enemy drawn from BOSS_SHAPE with `pos` as its top-left
+// corner, sweeping side to side and taking many hits to bring down
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct Boss {
+    pos: Pos,
+    hp: u32,
+    max_hp: u32,
+    direction: i8,
+}
+
+impl Boss {
+    // Cells covered by the boss's body, each with its glyph
+    fn cells(&self) -> impl Iterator<Item = (Pos, char)> + '_ {
+        BOSS_SHAPE.iter().enumerate().flat_map(move |(dy, row)| {
+            row.chars()
+                .enumerate()
+                .filter(|(_, c)| *c != ' ')
+                .map(move |(dx, c)| {
+                    let pos = Pos {
+                        x: self.pos.x + dx as u16,
+                        y: self.pos.y + dy as u16,
+                    };
+                    (pos, c)
+                })
+        })
     }
 
-    fn enemies_remaining(&self) -> usize {
-        self.enemies.len()
+    fn covers(&self, pos: Pos) -> bool {
+        self.cells().any(|(cell, _)| cell == pos)
     }
 
-    // Progress indicator (for the info panel)
-    fn progress(&self) -> f64 {
-        let total_expected = (self.spawn_rows * self.spawn_cols).max(1) + (self.level - 1) * 2;
-        (self.kills as f64 / total_expected as f64).min(1.0)
+    // Where its shots leave from: just below the middle of its body
+    fn muzzle(&self) -> Pos {
+        Pos {
+            x: self.pos.x + BOSS_WIDTH / 2,
+            y: self.pos.y + BOSS_SHAPE.len() as u16,
+        }
     }
 }
 
-// Draw the main play area
-fn draw_game<B: ratatui::backend::Backend>(f: &mut ratatui::Frame<B>, area: Rect, gs: &GameState) {
-    let block = Block::default().borders(Borders::ALL).title(Span::styled(
-        format!(" Space Invaders - Level {} ", gs.level),
-        Style::default()
-            .fg(Color::LightGreen)
-            .add_modifier(Modifier::BOLD),
-    ));
-    f.render_widget(block, area);
+// One block of a shield bunker, worn down a step by every bullet it stops
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct ShieldCell {
+    pos: Pos,
+    hp: u8,
+}
 
-    let inner = Rect {
-        x: area.x + 1,
-        y: area.y + 1,
-        width: area.width.saturating_sub(2),
-        height: area.height.saturating_sub(2),
-    };
+// A firework particle in board coordinates, moving by its velocity each tick.
+// Rockets climb until their fuse runs out, then burst into falling sparks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Firework {
+    x: f32,
+    y: f32,
+    vx: f32,
+    vy: f32,
+    color: Color,
+    rocket: bool,
+}
 
-    // Prepare 2D char grid for rendering entities
-    let mut grid = vec![vec![(' ', Style::default()); inner.width as usize]; inner.height as usize];
+// Enemy colors that stay readable next to yellow and white bullets and the cyan
+// player, and apart from the light red of an angry formation
+const ENEMY_PALETTE: [Color; 4] = [
+    Color::Red,
+    Color::Magenta,
+    Color::LightMagenta,
+    Color::Green,
+];
 
-    // Draw enemies
-    for e in &gs.enemies {
-        if e.x >= inner.x && e.y >= inner.y {
-            let lx = e.x - inner.x;
-            let ly = e.y - inner.y;
-            if lx < inner.width && ly < inner.height {
-                grid[ly as usize][lx as usize] = (
-                    '#',
-                    Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
-                );
-            }
-        }
-    }
+// An enemy shot; `dx` is its sideways drift per row (0 straight down), tracked
+// at sub-cell precision in `x` so angled shots follow their line
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct EnemyBullet {
+    pos: Pos,
+    x: f32,
+    dx: f32,
+}
 
-    // Draw bullets
-    for b in &gs.bullets {
-        if b.x >= inner.x && b.y >= inner.y {
-            let lx = b.x - inner.x;
-            let ly = b.y - inner.y;
-            if lx < inner.width && ly < inner.height {
-                grid[ly as usize][lx as usize] = (
-                    '|',
-                    Style::default()
-                        .fg(Color::Yellow)
-                        .add_modifier(Modifier::BOLD),
-                );
-            }
+impl EnemyBullet {
+    fn new(pos: Pos, dx: f32) -> Self {
+        EnemyBullet {
+            pos,
+            x: pos.x as f32,
+            dx,
         }
     }
+}
 
-    // Draw player
-    let p = &gs.player;
-    if p.x >= inner.x && p.y >= inner.y {
-        let lx = p.x - inner.x;
-        let ly = p.y - inner.y;
-        if lx < inner.width && ly < inner.height {
-            grid[ly as usize][lx as usize] = (
-                '^',
-                Style::default()
-                    .fg(Color::Cyan)
-                    .add_modifier(Modifier::BOLD),
-            );
-        }
-    }
+// Shape of an enemy volley
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BulletPattern {
+    // One shot straight down
+    Straight,
+    // Three shots fanning out
+    Spread,
+    // One shot along the line to the player's current position
+    Aimed,
+}
 
-    // Convert grid to styled text for ratatui Paragraph
-    let spans: Vec<Line> = grid
-        .iter()
-        .map(|row| {
-            Line::from(
-                row.iter()
-                    .map(|(c, s)| Span::styled(c.to_string(), *s))
-                    .collect::<Vec<_>>(),
-            )
-        })
-        .collect();
+// How the enemy formation picks which column leader fires next
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum FireStrategy {
+    Random,
+    // Cycle through the columns from left to right
+    RoundRobin,
+    // Always the leftmost column
+    Leftmost,
+    // Random, but heavily weighted toward columns at or near the player
+    Targeted,
+}
 
-    let play = Paragraph::new(spans).wrap(Wrap { trim: false });
-    f.render_widget(play, inner);
+// What happens when an enemy reaches the player's row
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum BreachPenalty {
+    // Classic rules: the game ends immediately
+    GameOver,
+    // The enemy is removed and the player loses a life
+    Life,
+    // The enemy is removed and the given points are deducted
+    Points(usize),
 }
 
-// Draw score, info panel, progress bar, etc.
-fn draw_ui<B: ratatui::backend::Backend>(f: &mut ratatui::Frame<B>, gs: &GameState) {
-    let size = f.size();
+// Difficulty for the levels from `level` on, until the next row of the table
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct LevelRow {
+    level: usize,
+    fire_every_ticks: u64,
+    move_every_ticks: u64,
+}
 
-    // Split screen into header and main section
-    let chunks = Layout::default()
-        .direction(Direction::Vertical)
-        .constraints([Constraint::Length(3), Constraint::Min(10)].as_ref())
-        .split(size);
+// One wave of a campaign: a hand-made formation and optional cadence overrides
+#[derive(Clone, Debug, PartialEq, Eq)]
+struct CampaignLevel {
+    name: String,
+    // Enemy cells relative to the formation's top-left corner
+    formation: Vec<Pos>,
+    fire_every_ticks: Option<u64>,
+    move_every_ticks: Option<u64>,
+}
 
-    // Header: score and controls
-    let score_text = Line::from(vec![
-        Span::raw(" Score: "),
-        Span::styled(gs.score.to_string(), Style::default().fg(Color::Yellow)),
-        Span::raw("  Enemies: "),
-        Span::styled(
-            gs.enemies_remaining().to_string(),
-            Style::default().fg(Color::Red),
-        ),
-        Span::raw("  Level: "),
-        Span::styled(gs.level.to_string(), Style::default().fg(Color::Green)),
-        Span::raw("  (q: quit, space: shoot, a/d or ←/→: move)"),
-    ]);
-    let header =
-        Paragraph::new(score_text).block(Block::default().borders(Borders::ALL).title(" Status "));
-    f.render_widget(header, chunks[0]);
+// When the sudden-death finale replaces the next wave
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum SuddenDeath {
+    // Once this level has been cleared
+    Level(usize),
+    // Once a wave is cleared with at least this score
+    Score(usize),
+}
 
-    // Split bottom area: left = game, right = info
-    let bottom = Layout::default()
-        .direction(Direction::Horizontal)
-        .constraints([Constraint::Percentage(75), Constraint::Percentage(25)].as_ref())
-        .split(chunks[1]);
+impl SuddenDeath {
+    fn reached(self, cleared_level: usize, score: usize) -> bool {
+        match self {
+            SuddenDeath::Level(level) => cleared_level >= level,
+            SuddenDeath::Score(min) => score >= min,
+        }
+    }
+}
 
-    draw_game(f, bottom[0], gs);
+// Game configuration parameters
+#[derive(Clone)]
+struct GameConfig {
+    tick_ms: u64,
+    initial_enemy_rows: usize,
+    initial_enemy_cols: usize,
+    // Waves smaller than this are topped up with extra enemies in free cells
+    min_wave_enemies: usize,
+    enemy_move_every_ticks: u64,
+    enemy_speedup_every_kills: usize,
+    starting_lives: usize,
+    enemy_breach_penalty: BreachPenalty,
+    // Optional finale: one wave at top speed under heavy fire, then victory with a bonus
+    sudden_death: Option<SuddenDeath>,
+    sudden_death_bonus: usize,
+    // Only an enemy landing on the player's own cell breaches, leaving room to dodge
+    // along the row; enemies past the row still breach
+    breach_on_contact: bool,
+    seed: u64,
+    randomize_enemy_colors: bool,
+    game_over_anim_ticks: u64,
+    // Ticks the ship can't be hit after losing a life
+    invulnerable_ticks: u64,
+    // Ticks of fireworks before the victory screen (0 = straight to it)
+    victory_anim_ticks: u64,
+    // Heat each trigger pull adds to the gauge, and heat shed per tick.
+    // An overheated gun only fires again once it has cooled all the way down (0 heat = no limit).
+    heat_per_shot: u32,
+    heat_cooling: u32,
+    // Ticks of charge a beam needs before release fires it (0 = no beam), and its recharge time
+    beam_charge_ticks: u64,
+    beam_cooldown_ticks: u64,
+    // Shots per magazine before a reload (0 = unlimited), and the reload time
+    magazine_size: usize,
+    reload_ticks: u64,
+    // Smart bombs held at the start (and at most), and how many rows above the ships a blast reaches
+    smart_bombs: usize,
+    bomb_reach: u16,
+    // Scores at which the weapon upgrades (double shot, then triple spread)
+    weapon_upgrade_scores: Vec<usize>,
+    key_bindings: Vec<(KeyCode, Action)>,
+    // Developer hotkeys (screen jumps and debug actions) respond
+    dev_keys: bool,
+    // Start in step mode, where the game only advances a tick per key press
+    debug_step: bool,
+    // Ticks between enemy shots (0 disables enemy fire)
+    enemy_fire_every_ticks: u64,
+    fire_strategy: FireStrategy,
+    // Play time after which a run returns to the menu (kiosk/demo setups)
+    max_duration_secs: Option<u64>,
+    show_reticle: bool,
+    // Score display: zero-pad to this many digits (0 = no padding)
+    score_pad_width: usize,
+    score_thousands_sep: bool,
+    // Ticks between swarm spawns (0 disables swarm enemies)
+    swarm_spawn_every_ticks: u64,
+    swarm_min_level: usize,
+    swarm_move_every_ticks: u64,
+    swarm_points: usize,
+    // Every this many levels the wave is a boss instead of a formation (0 = no bosses),
+    // with this much HP for the first boss and as much again for each one after
+    boss_every: usize,
+    boss_hp: u32,
+    // Ticks between enemies breaking formation to dive at the player (0 = no dives)
+    dive_every_ticks: u64,
+    // Ticks between mystery UFO flights across the top row (0 = no UFO)
+    ufo_every_ticks: u64,
+    // Percent chance a destroyed enemy drops a power-up (0 = none), and how long one lasts
+    powerup_chance: u32,
+    powerup_ticks: u64,
+    // Ticks between asteroids drifting down through the playfield (0 = no asteroids)
+    asteroid_every_ticks: u64,
+    // A bullet is spent on the first (lowest) enemy it hits instead of flying on
+    bullets_blocked_by_enemies: bool,
+    // Enemies a bullet destroys before it is spent, when bullets stop on hit (at least 1)
+    pierce_hits: u8,
+    scale_enemy_step: bool,
+    // Letterbox the playfield on wide terminals (None = use the full panel)
+    max_play_width: Option<u16>,
+    // Ticks turbo stays active after the last key repeat (there is no key-up event)
+    turbo_hold_ticks: u64,
+    // Scoring: kills are worth less the further a wave drags on (decays of 0 disable)
+    kill_points: usize,
+    kill_points_min: usize,
+    point_decay_per_wave_kill: usize,
+    point_decay_every_ticks: u64,
+    // Swap left/right input; optionally also flip the drawn playfield
+    mirror_controls: bool,
+    mirror_render: bool,
+    // Draw without colors, or in a theme's tints
+    mono: bool,
+    theme: Theme,
+    // Make the regular restart reuse the current seed
+    restart_same_seed: bool,
+    // The ship wraps from one edge of the board to the other instead of stopping at the walls
+    wrap_ship: bool,
+    max_enemy_bullets: usize,
+    // Enemy shots are stopped by enemies below the shooter, so only the front row hits home
+    formation_blocks_fire: bool,
+    // Volley shapes, cycled one per level
+    enemy_bullet_patterns: Vec<BulletPattern>,
+    // Wave shapes, likewise cycled one per level
+    formations: Vec<Formation>,
+    enemy_bullet_glyph: char,
+    enemy_bullet_color: Color,
+    // Cosmetic ground drawn along the row below the player (None = off)
+    ground: Option<(char, Color)>,
+    // Clearing this level wins the game (None = play until defeated)
+    max_level: Option<usize>,
+    // Ignore max_level and keep escalating enemy fire every wave
+    endless: bool,
+    // A second ship on its own keys shares the board, each with its own score and lives
+    coop: bool,
+    // Hot-seat versus: the second player's keys steer the formation and fire its
+    // columns instead of the automatic march and fire (replaces co-op)
+    versus: bool,
+    // Co-op ships draw on one life pool, ending the run when it's empty, instead of
+    // playing until both are out
+    shared_lives: bool,
+    // Score as much as possible before the time budget runs out (endless waves)
+    time_attack: bool,
+    time_attack_secs: u64,
+    // Cells moved per movement action at the start of a run (at least 1), and every
+    // this many levels the ships move a cell further (0 = only speed power-ups)
+    player_step: u16,
+    speed_up_every: usize,
+    // Each this many wall bounces in a wave, the descent grows by a row (0 = off)
+    descent_growth_every_bounces: u16,
+    // Upper bound on the rows dropped per bounce
+    max_descent: u16,
+    // Ticks the formation holds still after each descent (0 = keeps moving)
+    descent_pause_ticks: u64,
+    // Rows the drawn formation lags behind a descent before settling (0 = off),
+    // and over how many ticks it settles; purely visual
+    descent_squash: u16,
+    descent_squash_ticks: u64,
+    // The formation wraps around the side walls instead of bouncing, dropping
+    // after every `wrap_descent_every` full crossings
+    wrap_enemies: bool,
+    wrap_descent_every: u16,
+    // Allow angled shots (up-left / up-right) besides straight up
+    diagonal_shots: bool,
+    // Cue for a shot refused by an overheated gun: heat gauge flash color, and a terminal bell
+    blocked_shot_color: Color,
+    blocked_shot_bell: bool,
+    // Player and enemy bullets that meet destroy each other, as in the arcade original
+    bullets_cancel: bool,
+    // Kills within this many ticks of each other chain into a combo (0 = no combos)
+    combo_window_ticks: u64,
+    // A shot leaving the board without hitting anything also breaks the combo
+    combo_breaks_on_miss: bool,
+    // Bullets already in flight when each wave starts, as far as heat and ammo allow
+    opening_volley: usize,
+    // Shield bunkers between the player and the formation, built once per run (0 = none)
+    shields: usize,
+    // Bunker cells repaired at the start of each later wave (0 = damage stays)
+    shield_regen: usize,
+    // The player's own shots chip the bunkers too; when off they pass through
+    shields_absorb_player_shots: bool,
+    // Ticks the game freezes for on an impactful kill (0 = off)
+    hitstop_ticks: u16,
+    // Show a stats breakdown when a run ends, not just the score
+    end_summary: bool,
+    // Ticks a new wave takes to drop in from above (0 = appear instantly)
+    spawn_anim_ticks: u16,
+    // A landed wave waits for the shoot key before play resumes
+    wave_intro_wait_for_key: bool,
+    // Ticks the wave-complete interlude holds before the next wave (0 = none), and the
+    // accuracy bonus it pays for a perfect wave, scaled down with accuracy (0 = none)
+    wave_clear_ticks: u64,
+    accuracy_bonus: usize,
+    // Board border color while a wave is dropping in or waiting (None = no flash)
+    wave_intro_flash: Option<Color>,
+    // Enemies redden and flash as the formation nears the player row
+    enemy_anger: bool,
+    // Arrows on the board's edge point at hostiles outside the visible playfield
+    edge_markers: bool,
+    // Player bullets are colored by weapon tier and combo multiplier, not a single color
+    state_bullet_colors: bool,
+    // Show the next shot's path and the enemy it would hit; assisted runs are scored apart
+    aim_assist: bool,
+    // Twists each wave may be given, picked at random from the set (empty = off)
+    wave_modifiers: Vec<WaveModifier>,
+    // Ticks the formation moves at double speed after each kill (0 = off)
+    enemy_panic_ticks: u64,
+    // Per-level fire and movement cadences, sorted by level (empty = built-in curve)
+    level_table: Vec<LevelRow>,
+    // Hand-made waves played in order, the last one's clear winning the run (empty = off)
+    campaign: Vec<CampaignLevel>,
+    // Difficulty preselected on the menu; the menu's choice is stored back here for the run
+    difficulty: Difficulty,
+    // Local HTTP endpoint finished runs are posted to as JSON (opt-in)
+    submit_url: Option<String>,
+    // Print a one-line summary of the last run to stderr on exit
+    print_summary: bool,
+    // Fixed board size (including its border), independent of the terminal
+    virtual_size: Option<(u16, u16)>,
+    // Aim trainer: targets per session, and ticks per step of a moving target (0 = still)
+    trainer_targets: usize,
+    trainer_target_move_every_ticks: u64,
+    // Pause when the terminal reports losing focus (if it reports focus at all)
+    pause_on_focus_loss: bool,
+    // Where to write a replay of each finished run
+    replay_export: Option<PathBuf>,
+}
 
-    // Info panel with progress bar
-    let info_block = Block::default().borders(Borders::ALL).title(" Info ");
-    f.render_widget(info_block, bottom[1]);
+// Path of a file in the player's home directory (falling back to the working directory)
+fn data_path(name: &str) -> PathBuf {
+    std::env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_default()
+        .join(name)
+}
 
-    let inner = Rect {
-        x: bottom[1].x + 1,
-        y: bottom[1].y + 1,
-        width: bottom[1].width.saturating_sub(2),
-        height: bottom[1].height.saturating_sub(2),
-    };
+// Preferences changed in-game and saved between sessions. They're kept apart from
+// the config so a flag given for one session is never written back to the file.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+struct Prefs {
+    show_reticle: bool,
+    mirror_render: bool,
+    score_thousands_sep: bool,
+    aim_assist: bool,
+    wrap_ship: bool,
+    mono: bool,
+    theme: Theme,
+}
 
-    let g = Gauge::default()
-        .block(Block::default().borders(Borders::NONE))
-        .gauge_style(Style::default().fg(Color::Green))
-        .ratio(gs.progress());
-    f.render_widget(g, inner);
+impl Prefs {
+    // Location of the persisted preferences file
+    fn path() -> PathBuf {
+        data_path(".space_invaders_config")
+    }
 
-    // Show game over / win overlay
-    if gs.game_over || gs.victory {
-        let msg = if gs.victory { "YOU WIN!" } else { "GAME OVER" };
-        let rect = Rect {
-            x: size.x + (size.width / 2) - 15,
-            y: size.y + (size.height / 2) - 3,
-            width: 30,
-            height: 6,
+    // Saved preferences, with defaults for anything the file doesn't set
+    fn load() -> Self {
+        let mut prefs = Prefs::default();
+        let Ok(text) = fs::read_to_string(Self::path()) else {
+            return prefs;
         };
-        let block = Block::default().borders(Borders::ALL).title(Span::styled(
-            msg,
-            Style::default()
-                .fg(Color::Yellow)
-                .add_modifier(Modifier::BOLD),
-        ));
-        f.render_widget(block, rect);
-        let info = Paragraph::new(vec![
-            Line::from(format!("Final score: {}", gs.score)),
-            Line::from("Press 'r' to restart or 'q' to quit."),
-        ]);
-        f.render_widget(
-            info,
-            Rect {
-                x: rect.x + 1,
-                y: rect.y + 2,
-                width: rect.width - 2,
-                height: 3,
-            },
-        );
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let on = value.trim() == "true";
+            match key.trim() {
+                "show_reticle" => prefs.show_reticle = on,
+                "mirror_render" => prefs.mirror_render = on,
+                "score_thousands_sep" => prefs.score_thousands_sep = on,
+                "aim_assist" => prefs.aim_assist = on,
+                "wrap_ship" => prefs.wrap_ship = on,
+                "mono" => prefs.mono = on,
+                "theme" => prefs.theme = Theme::from_tag(value.trim()).unwrap_or_default(),
+                _ => {}
+            }
+        }
+        prefs
+    }
+
+    fn save(&self) -> io::Result<()> {
+        fs::write(
+            Self::path(),
+            format!(
+                "show_reticle={}\nmirror_render={}\nscore_thousands_sep={}\naim_assist={}\nwrap_ship={}\nmono={}\ntheme={}\n",
+                self.show_reticle,
+                self.mirror_render,
+                self.score_thousands_sep,
+                self.aim_assist,
+                self.wrap_ship,
+                self.mono,
+                self.theme.tag()
+            ),
+        )
+    }
+
+    // Apply on top of the default config, before command-line flags
+    fn apply(&self, cfg: &mut GameConfig) {
+        cfg.show_reticle = self.show_reticle;
+        cfg.mirror_render = self.mirror_render;
+        cfg.score_thousands_sep = self.score_thousands_sep;
+        cfg.aim_assist = self.aim_assist;
+        cfg.wrap_ship = self.wrap_ship;
+        cfg.mono = self.mono;
+        cfg.theme = self.theme;
+    }
+
+    // Take over one setting's value from the live config
+    fn update(&mut self, setting: Setting, cfg: &GameConfig) {
+        match setting {
+            Setting::Reticle => self.show_reticle = cfg.show_reticle,
+            Setting::MirrorView => self.mirror_render = cfg.mirror_render,
+            Setting::ScoreSeparators => self.score_thousands_sep = cfg.score_thousands_sep,
+            Setting::AimAssist => self.aim_assist = cfg.aim_assist,
+            Setting::WrapShip => self.wrap_ship = cfg.wrap_ship,
+            Setting::Mono => self.mono = cfg.mono,
+            Setting::Theme => self.theme = cfg.theme,
+        }
     }
 }
 
-fn main() -> Result<(), Box<dyn Error>> {
-    // Configure base game settings
-    let cfg = GameConfig {
-        tick_ms: 100,
-        initial_enemy_rows: 3,
-        initial_enemy_cols: 6,
-        enemy_move_every_ticks: 6,
-        enemy_speedup_every_kills: 5,
-    };
+impl GameConfig {
+    // Whether lives can be lost under the current rules
+    fn uses_lives(&self) -> bool {
+        self.enemy_breach_penalty == BreachPenalty::Life
+            || self.enemy_fire_every_ticks > 0
+            || self.level_table.iter().any(|row| row.fire_every_ticks > 0)
+            || self
+                .campaign
+                .iter()
+                .any(|l| l.fire_every_ticks.is_some_and(|t| t > 0))
+            || self.swarm_spawn_every_ticks > 0
+            || self.asteroid_every_ticks > 0
+            || self.sudden_death.is_some()
+    }
 
-    // Setup terminal in raw + alternate screen mode
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
-    let backend = CrosstermBackend::new(stdout);
-    let mut terminal = Terminal::new(backend)?;
-    terminal.clear()?;
+    // Whether an enemy at this position has broken through to the player
+    fn breaches(&self, enemy: Pos, player: Pos) -> bool {
+        if self.breach_on_contact {
+            enemy == player || enemy.y > player.y
+        } else {
+            enemy.y >= player.y
+        }
+    }
 
-    let size = terminal.size()?;
-    let mut gs = GameState::new(size.width, size.height, &cfg);
-
-    let tick_rate = Duration::from_millis(cfg.tick_ms);
-    let mut last_tick = Instant::now();
-
-    // Main event loop
-    loop {
-        terminal.draw(|f| draw_ui(f, &gs))?;
-
-        let timeout = tick_rate
-            .checked_sub(last_tick.elapsed())
-            .unwrap_or_else(|| Duration::from_millis(0));
-
-        // Handle keyboard and resize events
-        if event::poll(timeout)? {
-            match event::read()? {
-                Event::Key(KeyEvent {
-                    code, modifiers, ..
-                }) => match code {
-                    KeyCode::Char('q') => break,
-                    KeyCode::Char('a') | KeyCode::Left => gs.move_player_left(),
-                    KeyCode::Char('d') | KeyCode::Right => gs.move_player_right(),
-                    KeyCode::Char('r') => {
-                        if gs.game_over || gs.victory {
-                            gs.reset(&cfg);
-                        }
-                    }
-                    KeyCode::Char(' ') | KeyCode::Enter => {
-                        if !gs.game_over && !gs.victory {
-                            gs.shoot();
-                        }
-                    }
-                    KeyCode::Char('c') if modifiers == KeyModifiers::CONTROL => break,
-                    _ => {}
+    // Level table row in effect at a level; levels past the table keep its last row
+    fn level_row(&self, level: usize) -> Option<LevelRow> {
+        self.level_table
+            .iter()
+            .rev()
+            .find(|row| row.level <= level)
+            .or(self.level_table.first())
+            .copied()
+    }
+
+    // Campaign wave played at a level, if the campaign reaches that far
+    fn campaign_level(&self, level: usize) -> Option<&CampaignLevel> {
+        self.campaign.get(level.checked_sub(1)?)
+    }
+
+    // Level whose clear wins the run: a campaign's last wave, else --max-level
+    fn last_level(&self) -> Option<usize> {
+        if self.campaign.is_empty() {
+            self.max_level
+        } else {
+            Some(self.campaign.len())
+        }
+    }
+
+    // Action bound to a key, if any
+    fn action_for(&self, code: KeyCode) -> Option<Action> {
+        self.key_bindings
+            .iter()
+            .find(|(key, _)| *key == code)
+            .map(|(_, action)| *action)
+    }
+
+    // Action for a key during play: menu-only bindings are skipped, and the second
+    // player's keys are ignored in single-player but take precedence otherwise
+    fn play_action_for(&self, code: KeyCode) -> Option<Action> {
+        let mut bound = self
+            .key_bindings
+            .iter()
+            .filter(|(key, action)| *key == code && !action.menu_only())
+            .map(|(_, action)| *action);
+        if self.coop || self.versus {
+            let actions: Vec<Action> = bound.collect();
+            actions
+                .iter()
+                .find(|a| a.second_player())
+                .or(actions.first())
+                .copied()
+        } else {
+            bound.find(|a| !a.second_player())
+        }
+    }
+
+    // Tag for a two-player mode, kept apart from single-player scores
+    fn mode_tag(&self) -> Option<&'static str> {
+        if self.versus {
+            Some("versus")
+        } else if self.coop {
+            Some("coop")
+        } else {
+            None
+        }
+    }
+
+    // Format a score for display, e.g. "001230" or "1,230"
+    fn format_score(&self, score: usize) -> String {
+        let digits = format!("{score:0width$}", width = self.score_pad_width);
+        if !self.score_thousands_sep {
+            return digits;
+        }
+        let mut out = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                out.push(',');
+            }
+            out.push(c);
+        }
+        out
+    }
+
+    // All keys bound to an action, e.g. "a/←"
+    fn keys_for(&self, action: Action) -> String {
+        let keys: Vec<String> = self
+            .key_bindings
+            .iter()
+            .filter(|(_, a)| *a == action)
+            .map(|(key, _)| key_name(*key))
+            .collect();
+        if keys.is_empty() {
+            "-".to_string()
+        } else {
+            keys.join("/")
+        }
+    }
+}
+
+// Semantic actions the player can trigger, independent of the physical keys
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Action {
+    MoveLeft,
+    MoveRight,
+    Shoot,
+    ShootLeft,
+    ShootRight,
+    Reload,
+    SmartBomb,
+    // Second ship's controls, only active in co-op
+    P2Left,
+    P2Right,
+    P2Shoot,
+    Restart,
+    RestartSameSeed,
+    Menu,
+    ToggleReticle,
+    Turbo,
+    Pause,
+    ToggleHud,
+    ViewStats,
+    ResetStats,
+    Settings,
+    AimTrainer,
+    MenuUp,
+    MenuDown,
+    // Developer hotkeys, only active with --dev-keys
+    GoToMenu,
+    GoToGame,
+    GoToSettings,
+    GoToStats,
+    DebugClearWave,
+    DebugAddLife,
+    DebugStep,
+    DebugStepMode,
+    Quit,
+}
+
+impl Action {
+    const ALL: [Action; 32] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::Shoot,
+        Action::ShootLeft,
+        Action::ShootRight,
+        Action::Reload,
+        Action::SmartBomb,
+        Action::P2Left,
+        Action::P2Right,
+        Action::P2Shoot,
+        Action::Restart,
+        Action::RestartSameSeed,
+        Action::Menu,
+        Action::ToggleReticle,
+        Action::Turbo,
+        Action::Pause,
+        Action::ToggleHud,
+        Action::ViewStats,
+        Action::ResetStats,
+        Action::Settings,
+        Action::AimTrainer,
+        Action::MenuUp,
+        Action::MenuDown,
+        Action::GoToMenu,
+        Action::GoToGame,
+        Action::GoToSettings,
+        Action::GoToStats,
+        Action::DebugClearWave,
+        Action::DebugAddLife,
+        Action::DebugStep,
+        Action::DebugStepMode,
+        Action::Quit,
+    ];
+
+    // Actions that only do something on the menu screens
+    fn menu_only(self) -> bool {
+        matches!(
+            self,
+            Action::ViewStats
+                | Action::ResetStats
+                | Action::Settings
+                | Action::AimTrainer
+                | Action::MenuUp
+                | Action::MenuDown
+        )
+    }
+
+    // Developer hotkeys that jump between screens or tamper with a run; ignored
+    // unless enabled so casual players can't trigger them by accident
+    fn developer(self) -> bool {
+        matches!(
+            self,
+            Action::GoToMenu
+                | Action::GoToGame
+                | Action::GoToSettings
+                | Action::GoToStats
+                | Action::DebugClearWave
+                | Action::DebugAddLife
+                | Action::DebugStep
+                | Action::DebugStepMode
+        )
+    }
+
+    fn second_player(self) -> bool {
+        matches!(self, Action::P2Left | Action::P2Right | Action::P2Shoot)
+    }
+
+    // Screen-changing actions, where a held or bouncing key must not fire twice.
+    // Single-stepping is left out so holding its key steps repeatedly.
+    fn debounced(self) -> bool {
+        (self.developer() && self != Action::DebugStep)
+            || matches!(
+                self,
+                Action::Restart
+                    | Action::RestartSameSeed
+                    | Action::Menu
+                    | Action::ViewStats
+                    | Action::ResetStats
+                    | Action::Settings
+                    | Action::AimTrainer
+                    | Action::Quit
+            )
+    }
+
+    // Name used on the command line (--bind <name>=<keys>)
+    fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "left",
+            Action::MoveRight => "right",
+            Action::Shoot => "shoot",
+            Action::ShootLeft => "shoot-left",
+            Action::ShootRight => "shoot-right",
+            Action::Reload => "reload",
+            Action::SmartBomb => "bomb",
+            Action::P2Left => "p2-left",
+            Action::P2Right => "p2-right",
+            Action::P2Shoot => "p2-shoot",
+            Action::Restart => "restart",
+            Action::RestartSameSeed => "restart-seed",
+            Action::Menu => "menu",
+            Action::ToggleReticle => "reticle",
+            Action::Turbo => "turbo",
+            Action::Pause => "pause",
+            Action::ToggleHud => "hud",
+            Action::ViewStats => "stats",
+            Action::ResetStats => "reset-stats",
+            Action::Settings => "settings",
+            Action::AimTrainer => "trainer",
+            Action::MenuUp => "up",
+            Action::MenuDown => "down",
+            Action::GoToMenu => "go-menu",
+            Action::GoToGame => "go-game",
+            Action::GoToSettings => "go-settings",
+            Action::GoToStats => "go-stats",
+            Action::DebugClearWave => "debug-clear-wave",
+            Action::DebugAddLife => "debug-add-life",
+            Action::DebugStep => "debug-step",
+            Action::DebugStepMode => "debug-step-mode",
+            Action::Quit => "quit",
+        }
+    }
+
+    // Label shown in the controls panel
+    fn label(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::Shoot => "Shoot",
+            Action::ShootLeft => "Shoot up-left",
+            Action::ShootRight => "Shoot up-right",
+            Action::Reload => "Reload",
+            Action::SmartBomb => "Smart bomb",
+            Action::P2Left => "P2 left",
+            Action::P2Right => "P2 right",
+            Action::P2Shoot => "P2 shoot",
+            Action::Restart => "Restart",
+            Action::RestartSameSeed => "Same seed",
+            Action::Menu => "Menu",
+            Action::ToggleReticle => "Reticle",
+            Action::Turbo => "Turbo (hold)",
+            Action::Pause => "Pause",
+            Action::ToggleHud => "Hide HUD",
+            Action::ViewStats => "Stats",
+            Action::ResetStats => "Reset stats",
+            Action::Settings => "Settings",
+            Action::AimTrainer => "Aim trainer",
+            Action::MenuUp => "Menu up",
+            Action::MenuDown => "Menu down",
+            Action::GoToMenu => "Go to menu",
+            Action::GoToGame => "Go to game",
+            Action::GoToSettings => "Go to settings",
+            Action::GoToStats => "Go to stats",
+            Action::DebugClearWave => "Clear wave",
+            Action::DebugAddLife => "Extra life",
+            Action::DebugStep => "Step one tick",
+            Action::DebugStepMode => "Step mode on/off",
+            Action::Quit => "Quit",
+        }
+    }
+}
+
+// Default key bindings
+fn default_key_bindings() -> Vec<(KeyCode, Action)> {
+    vec![
+        (KeyCode::Char('a'), Action::MoveLeft),
+        (KeyCode::Left, Action::MoveLeft),
+        (KeyCode::Char('d'), Action::MoveRight),
+        (KeyCode::Right, Action::MoveRight),
+        (KeyCode::Char(' '), Action::Shoot),
+        (KeyCode::Enter, Action::Shoot),
+        (KeyCode::Char('z'), Action::ShootLeft),
+        (KeyCode::Char('c'), Action::ShootRight),
+        (KeyCode::Char('l'), Action::Reload),
+        (KeyCode::Char('b'), Action::SmartBomb),
+        (KeyCode::Char('r'), Action::Restart),
+        (KeyCode::Char('e'), Action::RestartSameSeed),
+        (KeyCode::Char('m'), Action::Menu),
+        (KeyCode::Char('t'), Action::ToggleReticle),
+        (KeyCode::Char('f'), Action::Turbo),
+        (KeyCode::Char('p'), Action::Pause),
+        (KeyCode::Esc, Action::Pause),
+        (KeyCode::Char('h'), Action::ToggleHud),
+        (KeyCode::Char('s'), Action::ViewStats),
+        (KeyCode::Char('x'), Action::ResetStats),
+        (KeyCode::Char('o'), Action::Settings),
+        (KeyCode::Char('i'), Action::AimTrainer),
+        (KeyCode::Up, Action::MenuUp),
+        (KeyCode::Char('k'), Action::MenuUp),
+        (KeyCode::Down, Action::MenuDown),
+        (KeyCode::Char('j'), Action::MenuDown),
+        (KeyCode::F(1), Action::GoToMenu),
+        (KeyCode::F(2), Action::GoToGame),
+        (KeyCode::F(3), Action::GoToSettings),
+        (KeyCode::F(4), Action::GoToStats),
+        (KeyCode::F(5), Action::DebugClearWave),
+        (KeyCode::F(6), Action::DebugAddLife),
+        (KeyCode::Char('.'), Action::DebugStep),
+        (KeyCode::Char(','), Action::DebugStepMode),
+        (KeyCode::Char('q'), Action::Quit),
+        // The second player's keys sit on the number row (or numpad), clear of every play key
+        (KeyCode::Char('4'), Action::P2Left),
+        (KeyCode::Char('6'), Action::P2Right),
+        (KeyCode::Char('5'), Action::P2Shoot),
+    ]
+}
+
+// Human-readable name for a key
+fn key_name(code: KeyCode) -> String {
+    match code {
+        KeyCode::Char(' ') => "space".to_string(),
+        KeyCode::Char(c) => c.to_string(),
+        KeyCode::Left => "←".to_string(),
+        KeyCode::Right => "→".to_string(),
+        KeyCode::Up => "↑".to_string(),
+        KeyCode::Down => "↓".to_string(),
+        KeyCode::Enter => "enter".to_string(),
+        KeyCode::Esc => "esc".to_string(),
+        KeyCode::Tab => "tab".to_string(),
+        KeyCode::F(n) => format!("F{n}"),
+        _ => "?".to_string(),
+    }
+}
+
+// Parse a key name as accepted by --bind
+fn parse_key(name: &str) -> Result<KeyCode, String> {
+    let code = match name {
+        "space" => KeyCode::Char(' '),
+        "enter" => KeyCode::Enter,
+        "esc" => KeyCode::Esc,
+        "tab" => KeyCode::Tab,
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        _ => {
+            let mut chars = name.chars();
+            match (chars.next(), chars.next()) {
+                (Some(c), None) => KeyCode::Char(c),
+                _ => match name.strip_prefix('f').and_then(|n| n.parse().ok()) {
+                    Some(n) => KeyCode::F(n),
+                    None => return Err(format!("unknown key: {name}")),
                 },
-                Event::Resize(w, h) => {
-                    gs.width = w;
-                    gs.height = h;
-                    gs.player.y = gs.height.saturating_sub(3);
+            }
+        }
+    };
+    Ok(code)
+}
+
+// Difficulty preset picked on the menu; scales speed, fire, wave size and points
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Difficulty {
+    Easy,
+    Normal,
+    Hard,
+}
+
+impl Difficulty {
+    const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+    fn label(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Normal => "normal",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Difficulty> {
+        Difficulty::ALL.into_iter().find(|d| d.tag() == tag)
+    }
+
+    // Neighbouring preset on the menu, stopping at either end
+    fn step(self, delta: isize) -> Difficulty {
+        let i = Difficulty::ALL.iter().position(|d| *d == self).unwrap_or(1);
+        Difficulty::ALL[i
+            .saturating_add_signed(delta)
+            .min(Difficulty::ALL.len() - 1)]
+    }
+
+    // Adjust the effective config for a run; Normal leaves it as configured
+    fn apply(self, cfg: &mut GameConfig) {
+        // Cadences scale by num/den, points by the inverse of the cadence
+        let (num, den, rows, cols): (u64, u64, isize, isize) = match self {
+            Difficulty::Easy => (3, 2, -1, -2),
+            Difficulty::Normal => return,
+            Difficulty::Hard => (2, 3, 1, 2),
+        };
+        let scale = |ticks: u64| (ticks * num / den).max(1);
+        let points = |p: usize| (p * den as usize / num as usize).max(1);
+        cfg.enemy_move_every_ticks = scale(cfg.enemy_move_every_ticks);
+        cfg.swarm_move_every_ticks = scale(cfg.swarm_move_every_ticks);
+        // Fire that is turned off stays off
+        if cfg.enemy_fire_every_ticks > 0 {
+            cfg.enemy_fire_every_ticks = scale(cfg.enemy_fire_every_ticks);
+        }
+        for row in &mut cfg.level_table {
+            row.move_every_ticks = scale(row.move_every_ticks);
+            if row.fire_every_ticks > 0 {
+                row.fire_every_ticks = scale(row.fire_every_ticks);
+            }
+        }
+        for level in &mut cfg.campaign {
+            level.move_every_ticks = level.move_every_ticks.map(scale);
+            level.fire_every_ticks = level
+                .fire_every_ticks
+                .map(|t| if t > 0 { scale(t) } else { 0 });
+        }
+        cfg.initial_enemy_rows = cfg
+            .initial_enemy_rows
+            .saturating_add_signed(rows)
+            .clamp(1, 6);
+        cfg.initial_enemy_cols = cfg
+            .initial_enemy_cols
+            .saturating_add_signed(cols)
+            .clamp(1, 12);
+        cfg.kill_points = points(cfg.kill_points);
+        cfg.kill_points_min = points(cfg.kill_points_min);
+        cfg.swarm_points = points(cfg.swarm_points);
+    }
+}
+
+// Optional challenge modifiers picked on the menu before a run
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Challenge {
+    DoubleSpeed,
+    OneLife,
+    EnemyFire,
+    BlockingBullets,
+    Mirror,
+    Endless,
+    TimeAttack,
+}
+
+impl Challenge {
+    const ALL: [Challenge; 7] = [
+        Challenge::DoubleSpeed,
+        Challenge::OneLife,
+        Challenge::EnemyFire,
+        Challenge::BlockingBullets,
+        Challenge::Mirror,
+        Challenge::Endless,
+        Challenge::TimeAttack,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Challenge::DoubleSpeed => "Double enemy speed",
+            Challenge::OneLife => "One life",
+            Challenge::EnemyFire => "Heavy enemy fire",
+            Challenge::BlockingBullets => "Bullets stop on hit",
+            Challenge::Mirror => "Mirrored controls",
+            Challenge::Endless => "Endless waves",
+            Challenge::TimeAttack => "Time attack",
+        }
+    }
+
+    // Short tag used to label runs and their best scores
+    fn tag(self) -> &'static str {
+        match self {
+            Challenge::DoubleSpeed => "fast",
+            Challenge::OneLife => "1life",
+            Challenge::EnemyFire => "fire",
+            Challenge::BlockingBullets => "block",
+            Challenge::Mirror => "mirror",
+            Challenge::Endless => "endless",
+            Challenge::TimeAttack => "time",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Challenge> {
+        Challenge::ALL.into_iter().find(|c| c.tag() == tag)
+    }
+
+    // Adjust the effective config for a run
+    fn apply(self, cfg: &mut GameConfig) {
+        match self {
+            Challenge::DoubleSpeed => {
+                cfg.enemy_move_every_ticks = (cfg.enemy_move_every_ticks / 2).max(1);
+                cfg.swarm_move_every_ticks = (cfg.swarm_move_every_ticks / 2).max(1);
+                for row in &mut cfg.level_table {
+                    row.move_every_ticks = (row.move_every_ticks / 2).max(1);
+                }
+                for level in &mut cfg.campaign {
+                    level.move_every_ticks = level.move_every_ticks.map(|t| (t / 2).max(1));
                 }
-                _ => {}
+            }
+            Challenge::OneLife => cfg.starting_lives = 1,
+            // Twice the fire, or fire at all if it was turned off
+            Challenge::EnemyFire => {
+                cfg.enemy_fire_every_ticks = match cfg.enemy_fire_every_ticks {
+                    0 => 12,
+                    ticks => (ticks / 2).max(MIN_ENDLESS_FIRE_TICKS),
+                };
+            }
+            Challenge::BlockingBullets => cfg.bullets_blocked_by_enemies = true,
+            Challenge::Mirror => cfg.mirror_controls = true,
+            Challenge::Endless => cfg.endless = true,
+            Challenge::TimeAttack => {
+                cfg.time_attack = true;
+                cfg.endless = true;
             }
         }
+    }
+}
 
-        // Tick game logic at fixed interval
-        if last_tick.elapsed() >= tick_rate {
-            gs.tick(&cfg);
-            if gs.kills > 0 && gs.kills % cfg.enemy_speedup_every_kills == 0 {
-                gs.enemy_move_every_ticks = gs.enemy_move_every_ticks.saturating_sub(1).max(1);
+// Power-up a destroyed enemy may drop; the player collects it by touching it
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum PowerUpKind {
+    // Triple spread regardless of the weapon level
+    Spread,
+    // Twice as many bullets in flight
+    Rapid,
+    // Absorbs the next hit
+    Shield,
+    // Permanently moves the ships a cell further per input, up to a cap
+    Speed,
+}
+
+impl PowerUpKind {
+    const ALL: [PowerUpKind; 4] = [
+        PowerUpKind::Spread,
+        PowerUpKind::Rapid,
+        PowerUpKind::Shield,
+        PowerUpKind::Speed,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            PowerUpKind::Spread => "Spread",
+            PowerUpKind::Rapid => "Rapid",
+            PowerUpKind::Shield => "Shield",
+            PowerUpKind::Speed => "Speed",
+        }
+    }
+
+    fn glyph(self) -> char {
+        match self {
+            PowerUpKind::Spread => 'S',
+            PowerUpKind::Rapid => 'R',
+            PowerUpKind::Shield => 'O',
+            PowerUpKind::Speed => 'V',
+        }
+    }
+
+    fn color(self) -> Color {
+        match self {
+            PowerUpKind::Spread => Color::LightYellow,
+            PowerUpKind::Rapid => Color::LightCyan,
+            PowerUpKind::Shield => Color::LightBlue,
+            PowerUpKind::Speed => Color::LightGreen,
+        }
+    }
+}
+
+// Twist a wave can be given when wave modifiers are enabled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum WaveModifier {
+    // The formation steps twice as often
+    Fast,
+    // Kills score twice the points
+    DoublePoints,
+    // Enemies are drawn dimmed
+    Fog,
+    // Enemies fire twice as often (only offered while they fire at all)
+    HeavyFire,
+}
+
+impl WaveModifier {
+    const ALL: [WaveModifier; 4] = [
+        WaveModifier::Fast,
+        WaveModifier::DoublePoints,
+        WaveModifier::Fog,
+        WaveModifier::HeavyFire,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            WaveModifier::Fast => "Fast Wave",
+            WaveModifier::DoublePoints => "Double Points",
+            WaveModifier::Fog => "Fog",
+            WaveModifier::HeavyFire => "Heavy Fire",
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            WaveModifier::Fast => "fast",
+            WaveModifier::DoublePoints => "double",
+            WaveModifier::Fog => "fog",
+            WaveModifier::HeavyFire => "heavy",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<WaveModifier> {
+        WaveModifier::ALL.into_iter().find(|m| m.tag() == tag)
+    }
+}
+
+// Label for a kind of run, e.g. "L1" or "L3+hard+fast+1life"; scores only compare within a label.
+// Normal difficulty adds nothing; two-player runs and runs played with the aim assist are labelled apart.
+fn run_tag(
+    start_level: usize,
+    difficulty: Difficulty,
+    challenges: &[Challenge],
+    mode: Option<&str>,
+    assisted: bool,
+) -> String {
+    let mut tag = format!("L{start_level}");
+    if difficulty != Difficulty::Normal {
+        tag.push('+');
+        tag.push_str(difficulty.tag());
+    }
+    for c in challenges {
+        tag.push('+');
+        tag.push_str(c.tag());
+    }
+    if let Some(mode) = mode {
+        tag.push('+');
+        tag.push_str(mode);
+    }
+    if assisted {
+        tag.push_str("+assist");
+    }
+    tag
+}
+
+// Selections on the start menu
+struct MenuState {
+    start_level: usize,
+    difficulty: Difficulty,
+    // 0 is the start-level row, 1 the difficulty row, the rest are challenge rows
+    row: usize,
+    challenges: Vec<Challenge>,
+}
+
+impl MenuState {
+    fn toggle_challenge(&mut self, challenge: Challenge) {
+        if let Some(i) = self.challenges.iter().position(|c| *c == challenge) {
+            self.challenges.remove(i);
+        } else {
+            self.challenges.push(challenge);
+            self.challenges.sort();
+        }
+    }
+}
+
+// Which screen the main loop is currently showing
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Screen {
+    Menu,
+    // Persisted stats, optionally asking to confirm a reset
+    Stats { confirm_reset: bool },
+    // Runtime settings, with the selected row; opened from the pause overlay, it returns to the paused game
+    Settings { row: usize, from_pause: bool },
+    Playing,
+}
+
+// Settings that can be toggled at runtime and are saved to the prefs file. Any that
+// change gameplay, like wrap-around, must be written into the replay args by
+// `Replay::from_run`, or replays of those runs won't play back the same.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Setting {
+    Reticle,
+    MirrorView,
+    ScoreSeparators,
+    AimAssist,
+    WrapShip,
+    Mono,
+    Theme,
+}
+
+impl Setting {
+    const ALL: [Setting; 7] = [
+        Setting::Reticle,
+        Setting::MirrorView,
+        Setting::ScoreSeparators,
+        Setting::AimAssist,
+        Setting::WrapShip,
+        Setting::Mono,
+        Setting::Theme,
+    ];
+
+    fn label(self) -> &'static str {
+        match self {
+            Setting::Reticle => "Aim reticle",
+            Setting::MirrorView => "Mirrored view",
+            Setting::ScoreSeparators => "Score separators",
+            Setting::AimAssist => "Aim assist",
+            Setting::WrapShip => "Wrap-around ship",
+            Setting::Mono => "Monochrome",
+            Setting::Theme => "Theme",
+        }
+    }
+
+    // The row on the settings screen: a checkbox for a toggle, the current pick for the theme
+    fn row(self, cfg: &GameConfig) -> String {
+        let on = match self {
+            Setting::Reticle => cfg.show_reticle,
+            Setting::MirrorView => cfg.mirror_render,
+            Setting::ScoreSeparators => cfg.score_thousands_sep,
+            Setting::AimAssist => cfg.aim_assist,
+            Setting::WrapShip => cfg.wrap_ship,
+            Setting::Mono => cfg.mono,
+            Setting::Theme => return format!(" {}: {}", self.label(), cfg.theme.label()),
+        };
+        let mark = if on { "[x]" } else { "[ ]" };
+        format!(" {mark} {}", self.label())
+    }
+
+    // Read only when a run starts, so a change made from the pause overlay waits for the next run
+    fn next_run_only(self) -> bool {
+        self == Setting::WrapShip
+    }
+
+    // Flip a toggle, or move on to the next theme
+    fn advance(self, cfg: &mut GameConfig) {
+        match self {
+            Setting::Reticle => cfg.show_reticle = !cfg.show_reticle,
+            Setting::MirrorView => cfg.mirror_render = !cfg.mirror_render,
+            Setting::ScoreSeparators => cfg.score_thousands_sep = !cfg.score_thousands_sep,
+            Setting::AimAssist => cfg.aim_assist = !cfg.aim_assist,
+            Setting::WrapShip => cfg.wrap_ship = !cfg.wrap_ship,
+            Setting::Mono => cfg.mono = !cfg.mono,
+            Setting::Theme => cfg.theme = cfg.theme.next(),
+        }
+    }
+
+    // Give `to` this setting's value in `from`
+    fn copy(self, from: &GameConfig, to: &mut GameConfig) {
+        match self {
+            Setting::Reticle => to.show_reticle = from.show_reticle,
+            Setting::MirrorView => to.mirror_render = from.mirror_render,
+            Setting::ScoreSeparators => to.score_thousands_sep = from.score_thousands_sep,
+            Setting::AimAssist => to.aim_assist = from.aim_assist,
+            Setting::WrapShip => to.wrap_ship = from.wrap_ship,
+            Setting::Mono => to.mono = from.mono,
+            Setting::Theme => to.theme = from.theme,
+        }
+    }
+}
+
+// Color themes: the game's own colors, or everything tinted like an old
+// single-color monitor
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+enum Theme {
+    #[default]
+    Classic,
+    Amber,
+    Green,
+}
+
+impl Theme {
+    const ALL: [Theme; 3] = [Theme::Classic, Theme::Amber, Theme::Green];
+
+    fn label(self) -> &'static str {
+        match self {
+            Theme::Classic => "Classic",
+            Theme::Amber => "Amber",
+            Theme::Green => "Green",
+        }
+    }
+
+    fn tag(self) -> &'static str {
+        match self {
+            Theme::Classic => "classic",
+            Theme::Amber => "amber",
+            Theme::Green => "green",
+        }
+    }
+
+    fn from_tag(tag: &str) -> Option<Theme> {
+        Theme::ALL.into_iter().find(|t| t.tag() == tag)
+    }
+
+    // The theme after this one on the settings screen, wrapping around
+    fn next(self) -> Theme {
+        let i = Theme::ALL.iter().position(|t| *t == self).unwrap_or(0);
+        Theme::ALL[(i + 1) % Theme::ALL.len()]
+    }
+
+    // How `color` is drawn under this theme: bright colors take the bright tint and
+    // the rest the dim one, while black, dark gray and the terminal default stay as they are
+    fn tint(self, color: Color) -> Color {
+        let (bright, dim) = match self {
+            Theme::Classic => return color,
+            Theme::Amber => (Color::LightYellow, Color::Yellow),
+            Theme::Green => (Color::LightGreen, Color::Green),
+        };
+        match color {
+            Color::Reset | Color::Black | Color::DarkGray => color,
+            Color::Red
+            | Color::Green
+            | Color::Yellow
+            | Color::Blue
+            | Color::Magenta
+            | Color::Cyan
+            | Color::Gray => dim,
+            _ => bright,
+        }
+    }
+}
+
+// Recolors everything drawn so far in a frame: a theme tints every color, and
+// monochrome drops them altogether, leaving only bold and reversed text to stand out
+struct Recolor {
+    theme: Theme,
+    mono: bool,
+}
+
+impl Widget for Recolor {
+    fn render(self, area: Rect, buf: &mut Buffer) {
+        for y in area.top()..area.bottom() {
+            for x in area.left()..area.right() {
+                let cell = buf.get_mut(x, y);
+                if self.mono {
+                    cell.set_fg(Color::Reset).set_bg(Color::Reset);
+                } else {
+                    let (fg, bg) = (self.theme.tint(cell.fg), self.theme.tint(cell.bg));
+                    cell.set_fg(fg).set_bg(bg);
+                }
             }
-            if gs.enemies.is_empty() {
-                gs.victory = true;
+        }
+    }
+}
+
+// Progress persisted between runs
+#[derive(Default)]
+struct Stats {
+    highest_level: usize,
+    games_played: usize,
+    // Best score per run tag (start level and challenges), so runs are only compared with like runs
+    best_scores: BTreeMap<String, usize>,
+    // Score at each elapsed second of the best run per tag, raced as a ghost
+    ghosts: BTreeMap<String, Vec<usize>>,
+}
+
+impl Stats {
+    // Location of the stats file
+    fn path() -> PathBuf {
+        data_path(".space_invaders_stats")
+    }
+
+    // Load stats from disk, starting fresh if the file is missing or unreadable
+    fn load() -> Self {
+        let mut stats = Stats {
+            highest_level: 1,
+            ..Stats::default()
+        };
+        let Ok(text) = fs::read_to_string(Self::path()) else {
+            return stats;
+        };
+        for line in text.lines() {
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            if let Some(tag) = key.trim().strip_prefix("ghost.") {
+                let samples = value.split(',').map(|v| v.trim().parse()).collect();
+                if let Ok(samples) = samples {
+                    stats.ghosts.insert(tag.to_string(), samples);
+                }
+                continue;
+            }
+            let Ok(value) = value.trim().parse::<usize>() else {
+                continue;
+            };
+            match key.trim() {
+                "highest_level" => stats.highest_level = value.max(1),
+                "games_played" => stats.games_played = value,
+                key => {
+                    if let Some(tag) = key.strip_prefix("best_score.") {
+                        stats.best_scores.insert(tag.to_string(), value);
+                    }
+                }
             }
-            last_tick = Instant::now();
         }
+        stats
     }
 
-    // Restore terminal before exiting
-    disable_raw_mode()?;
-    execute!(
-        terminal.backend_mut(),
-        LeaveAlternateScreen,
-        DisableMouseCapture
-    )?;
-    terminal.show_cursor()?;
-    println!("Thanks for playing! Final score: {}", gs.score);
-    Ok(())
+    // Write stats to disk as simple key=value lines
+    fn save(&self) -> io::Result<()> {
+        let mut text = format!(
+            "highest_level={}\ngames_played={}\n",
+            self.highest_level, self.games_played
+        );
+        for (tag, score) in &self.best_scores {
+            text.push_str(&format!("best_score.{tag}={score}\n"));
+        }
+        for (tag, samples) in &self.ghosts {
+            let samples: Vec<String> = samples.iter().map(|s| s.to_string()).collect();
+            text.push_str(&format!("ghost.{tag}={}\n", samples.join(",")));
+        }
+        fs::write(Self::path(), text)
+    }
+
+    // Forget all progress, removing the file from disk
+    fn clear(&mut self) -> io::Result<()> {
+        *self = Stats {
+            highest_level: 1,
+            ..Stats::default()
+        };
+        match fs::remove_file(Self::path()) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+
+    // Best score across all start levels
+    fn high_score(&self) -> usize {
+        self.best_scores.values().copied().max().unwrap_or(0)
+    }
+
+    fn best_score(&self, tag: &str) -> usize {
+        self.best_scores.get(tag).copied().unwrap_or(0)
+    }
+
+    // Record a run once and persist, ignoring write failures
+    fn finish_run(&mut self, gs: &mut GameState) {
+        if !gs.run_recorded {
+            gs.run_recorded = true;
+            gs.new_best = gs.score > self.best_score(&gs.run_tag());
+            self.record(gs);
+            self.save().ok();
+        }
+    }
+
+    // Fold a finished run into the stats
+    fn record(&mut self, gs: &GameState) {
+        self.games_played += 1;
+        self.highest_level = self.highest_level.max(gs.level);
+        let best = self.best_scores.entry(gs.run_tag()).or_insert(0);
+        // A new best becomes the ghost that later runs of the same kind race against
+        if gs.score > *best && !gs.score_samples.is_empty() {
+            self.ghosts.insert(gs.run_tag(), gs.score_samples.clone());
+        }
+        *best = (*best).max(gs.score);
+    }
+
+    // How far the run is ahead of (positive) or behind its ghost, once there is one
+    fn ghost_lead(&self, gs: &GameState) -> Option<i64> {
+        let ghost = self.ghosts.get(&gs.run_tag())?;
+        let second = (gs.elapsed_ms / 1000) as usize;
+        if second == 0 {
+            return None;
+        }
+        // Past the end of the ghost run, compare with its final score
+        let ghost_score = ghost.get(second - 1).or(ghost.last())?;
+        Some(gs.score as i64 - *ghost_score as i64)
+    }
+}
+
+// Version written to and required from replay files
+const REPLAY_VERSION: u32 = 16;
+
+// One player input, recorded with the tick count it arrived at
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ReplayInput {
+    Left,
+    Right,
+    Shoot,
+    Turbo,
+    ShootLeft,
+    ShootRight,
+    Resize(u16, u16),
+    SkipSpawn,
+    Reload,
+    Bomb,
+    P2Left,
+    P2Right,
+    P2Shoot,
+}
+
+impl ReplayInput {
+    fn encode(self) -> String {
+        match self {
+            ReplayInput::Left => "L".into(),
+            ReplayInput::Right => "R".into(),
+            ReplayInput::Shoot => "S".into(),
+            ReplayInput::ShootLeft => "SL".into(),
+            ReplayInput::ShootRight => "SR".into(),
+            ReplayInput::Turbo => "T".into(),
+            ReplayInput::Resize(w, h) => format!("Z {w} {h}"),
+            ReplayInput::SkipSpawn => "K".into(),
+            ReplayInput::Reload => "RL".into(),
+            ReplayInput::Bomb => "B".into(),
+            ReplayInput::P2Left => "2L".into(),
+            ReplayInput::P2Right => "2R".into(),
+            ReplayInput::P2Shoot => "2S".into(),
+        }
+    }
+
+    fn decode(fields: &[&str]) -> Option<ReplayInput> {
+        match fields {
+            ["L"] => Some(ReplayInput::Left),
+            ["R"] => Some(ReplayInput::Right),
+            ["S"] => Some(ReplayInput::Shoot),
+            ["SL"] => Some(ReplayInput::ShootLeft),
+            ["SR"] => Some(ReplayInput::ShootRight),
+            ["T"] => Some(ReplayInput::Turbo),
+            ["K"] => Some(ReplayInput::SkipSpawn),
+            ["RL"] => Some(ReplayInput::Reload),
+            ["B"] => Some(ReplayInput::Bomb),
+            ["2L"] => Some(ReplayInput::P2Left),
+            ["2R"] => Some(ReplayInput::P2Right),
+            ["2S"] => Some(ReplayInput::P2Shoot),
+            ["Z", w, h] => Some(ReplayInput::Resize(w.parse().ok()?, h.parse().ok()?)),
+            _ => None,
+        }
+    }
+}
+
+// A recorded run: everything needed to play it back deterministically.
+// Stored as plain text: a header carrying REPLAY_VERSION, one `key value` per line,
+// then one `tick input` per line (input codes as in `ReplayInput::encode`):
+//
+//   space-invaders-replay 16
+//   seed 42
+//   board 60 20
+//   start-level 1
+//   difficulty normal
+//   challenges fast mirror
+//   arg --lives
+//   arg 5
+//   score 320
+//   inputs
+//   0 L
+//   12 S
+//   30 RL
+//   40 Z 70 22
+//   41 2S
+struct Replay {
+    seed: u64,
+    width: u16,
+    height: u16,
+    start_level: usize,
+    difficulty: Difficulty,
+    challenges: Vec<Challenge>,
+    // Command-line settings of the run, replayed through `parse_args`
+    args: Vec<String>,
+    score: usize,
+    inputs: Vec<(u64, ReplayInput)>,
+}
+
+impl Replay {
+    // Wrap-around is an in-game setting, so it is added to the recorded args when on
+    fn from_run(gs: &GameState, args: &[String]) -> Self {
+        let mut args = args.to_vec();
+        if gs.wrap_ship && !args.iter().any(|a| a == "--wrap-ship") {
+            args.push("--wrap-ship".into());
+        }
+        Replay {
+            seed: gs.seed,
+            width: gs.start_size.0,
+            height: gs.start_size.1,
+            start_level: gs.start_level,
+            difficulty: gs.difficulty,
+            challenges: gs.challenges.clone(),
+            args,
+            score: gs.score,
+            inputs: gs.inputs.clone(),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        let mut out = format!("space-invaders-replay {REPLAY_VERSION}\n");
+        out += &format!("seed {}\n", self.seed);
+        out += &format!("board {} {}\n", self.width, self.height);
+        out += &format!("start-level {}\n", self.start_level);
+        out += &format!("difficulty {}\n", self.difficulty.tag());
+        let tags: Vec<&str> = self.challenges.iter().map(|c| c.tag()).collect();
+        out += &format!("challenges {}\n", tags.join(" "));
+        for arg in &self.args {
+            out += &format!("arg {arg}\n");
+        }
+        out += &format!("score {}\n", self.score);
+        out += "inputs\n";
+        for (tick, input) in &self.inputs {
+            out += &format!("{tick} {}\n", input.encode());
+        }
+        out
+    }
+
+    fn parse(text: &str) -> Result<Self, String> {
+        let mut lines = text.lines().enumerate();
+        let version = lines
+            .next()
+            .and_then(|(_, l)| l.strip_prefix("space-invaders-replay "))
+            .ok_or("not a space invaders replay file")?;
+        if version.trim() != REPLAY_VERSION.to_string() {
+            return Err(format!(
+                "replay format version {} is not supported (expected {REPLAY_VERSION})",
+                version.trim()
+            ));
+        }
+        let mut replay = Replay {
+            seed: 0,
+            width: 0,
+            height: 0,
+            start_level: 1,
+            difficulty: Difficulty::Normal,
+            challenges: Vec::new(),
+            args: Vec::new(),
+            score: 0,
+            inputs: Vec::new(),
+        };
+        let mut in_inputs = false;
+        for (n, line) in lines {
+            let bad = || format!("replay line {}: invalid entry: {line}", n + 1);
+            if in_inputs {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let (tick, input) = fields.split_first().ok_or_else(bad)?;
+                let tick = tick.parse().map_err(|_| bad())?;
+                let input = ReplayInput::decode(input).ok_or_else(bad)?;
+                replay.inputs.push((tick, input));
+                continue;
+            }
+            let (key, value) = line.split_once(' ').unwrap_or((line, ""));
+            match key {
+                "seed" => replay.seed = value.parse().map_err(|_| bad())?,
+                "board" => {
+                    let (w, h) = value.split_once(' ').ok_or_else(bad)?;
+                    replay.width = w.parse().map_err(|_| bad())?;
+                    replay.height = h.parse().map_err(|_| bad())?;
+                }
+                "start-level" => replay.start_level = value.parse().map_err(|_| bad())?,
+                "difficulty" => replay.difficulty = Difficulty::from_tag(value).ok_or_else(bad)?,
+                "challenges" => {
+                    replay.challenges = value
+                        .split_whitespace()
+                        .map(|t| Challenge::from_tag(t).ok_or_else(bad))
+                        .collect::<Result<_, _>>()?
+                }
+                "arg" => replay.args.push(value.to_string()),
+                "score" => replay.score = value.parse().map_err(|_| bad())?,
+                "inputs" => in_inputs = true,
+                _ => return Err(bad()),
+            }
+        }
+        if replay.width == 0 || replay.height == 0 {
+            return Err("replay is missing its board size".into());
+        }
+        Ok(replay)
+    }
+
+    fn save(&self, path: &Path) -> io::Result<()> {
+        fs::write(path, self.to_text())
+    }
+
+    fn load(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path)
+            .map_err(|e| format!("cannot read replay {}: {e}", path.display()))?;
+        Replay::parse(&text).map_err(|e| format!("{}: {e}", path.display()))
+    }
+}
+
+// Aim-trainer progress: the current target, how long each hit took to land
+struct Trainer {
+    target: Pos,
+    // Sideways direction of a moving target
+    direction: i8,
+    spawned_tick: u64,
+    remaining: usize,
+    reaction_ticks: Vec<u64>,
+}
+
+impl Trainer {
+    // Average ticks from a target appearing to it being hit
+    fn average_reaction_ticks(&self) -> u64 {
+        let total: u64 = self.reaction_ticks.iter().sum();
+        total
+            .checked_div(self.reaction_ticks.len() as u64)
+            .unwrap_or(0)
+    }
+}
+
+// Holds all dynamic game state
+struct GameState {
+    width: u16,
+    height: u16,
+    ships: Vec<Ship>,
+    bullets: Vec<Bullet>,
+    enemy_bullets: Vec<EnemyBullet>,
+    enemies: Vec<Enemy>,
+    // Boss of a boss wave, which has no formation
+    boss: Option<Boss>,
+    shields: Vec<ShieldCell>,
+    // While positive, a shot was just refused because the gun was overheated
+    blocked_shot: u8,
+    // Tick of the last shot press, the beam charge built so far while charging,
+    // ticks until the beam can charge again, and the column of a beam still on screen
+    last_shot_tick: Option<u64>,
+    beam_charge: Option<u64>,
+    beam_cooldown: u64,
+    beam: Option<(u16, u8)>,
+    // Shots left in the magazine, and ticks until a running reload refills it
+    ammo_in_mag: usize,
+    reload_ticks: u64,
+    // Smart bombs left; one comes back each level
+    bombs: usize,
+    // Cells the ships move per movement input, raised by level and speed power-ups
+    ship_speed: u16,
+    // Aim-trainer session in place of the regular game, if one is running
+    trainer: Option<Trainer>,
+    // While positive the game is frozen for a beat after an impactful kill
+    hitstop: u16,
+    // While positive the formation is panicking from a recent kill and moves faster
+    enemy_panic: u64,
+    // Twist given to the current wave, if any
+    wave_modifier: Option<WaveModifier>,
+    // Shape of the current regular wave
+    formation: Formation,
+    // The current wave is the sudden-death finale
+    sudden_death: bool,
+    // Grid spots of the wave's enemies while they drop in, and the ticks left to land
+    enemy_targets: Vec<Enemy>,
+    spawn_anim: u16,
+    // A landed wave is holding play until the player is ready
+    awaiting_ready: bool,
+    // Ticks left of the wave-complete interlude, and the cleared wave's accuracy and bonus
+    wave_clear_anim: u64,
+    wave_accuracy: Option<(usize, usize)>,
+    // Fast enemies that ignore the formation and home in on the player
+    swarm: Vec<Pos>,
+    // Mystery UFO crossing the top row, and the direction it flies in
+    ufo: Option<(Pos, i8)>,
+    // Power-ups falling toward the player, and collected ones with their ticks left
+    powerups: Vec<(Pos, PowerUpKind)>,
+    buffs: Vec<(PowerUpKind, u64)>,
+    // Asteroids drifting down, which stop every bullet and wreck any ship they touch
+    obstacles: Vec<Pos>,
+    // Recent events, newest first, each with its remaining TTL in ticks
+    events: Vec<(String, u8)>,
+    // Where bullets recently cancelled out, newest first, with TTLs
+    sparks: Vec<(Pos, u8)>,
+    score: usize,
+    kills: usize,
+    wave_kills: usize,
+    wave_start_tick: u64,
+    // Shots fired this wave, and how many of them hit something
+    wave_shots: usize,
+    wave_hits: usize,
+    // Wall bounces (or full wraps) of the current wave's formation
+    wave_bounces: u16,
+    // Ticks the formation still holds still after its last descent
+    descent_pause: u64,
+    // Ticks left in the drawn settle after a descent, and the rows it dropped
+    descent_anim: u64,
+    descent_drop: u16,
+    // Cells the wrapping formation has moved since its last full crossing
+    wrap_shift: u16,
+    weapon_level: usize,
+    tick_count: u64,
+    // While positive the whole game runs at double speed
+    turbo_ticks: u64,
+    // Play time in milliseconds; only advances while the game is ticking
+    elapsed_ms: u64,
+    // Real time the run has been live, at the tick rate each tick actually ran at (so
+    // turbo counts half) and including holds between waves; excludes pauses
+    live_ms: u64,
+    // Time attack: extra time earned by quick clears, and whether the clock ran out
+    time_bonus_ms: u64,
+    time_up: bool,
+    enemy_tick_acc: u64,
+    enemy_move_every_ticks: u64,
+    enemy_direction: i8,
+    game_over: bool,
+    game_over_anim: u64,
+    victory: bool,
+    // Ticks left of the victory fireworks, and their particles with ticks to live
+    victory_anim: u64,
+    fireworks: Vec<(Firework, u8)>,
+    spawn_rows: usize,
+    spawn_cols: usize,
+    level: usize,
+    fire_cursor: usize,
+    start_level: usize,
+    difficulty: Difficulty,
+    challenges: Vec<Challenge>,
+    // Played with the aim assist, so scored apart from unassisted runs
+    assisted: bool,
+    // The invaders are steered by a second player, who must wait this many ticks between volleys
+    versus: bool,
+    versus_reload: u64,
+    // Ships wrap around the board edges; fixed for the run, since settings change between runs
+    wrap_ship: bool,
+    run_recorded: bool,
+    // Kills in the current chain, ticks left to extend it, and the longest chain
+    combo: usize,
+    combo_ticks: u64,
+    best_combo: usize,
+    // Run totals for the end-of-run summary
+    shots_fired: usize,
+    shots_hit: usize,
+    waves_cleared: usize,
+    new_best: bool,
+    // Score at the end of each elapsed second, kept as the ghost if the run is a new best
+    score_samples: Vec<usize>,
+    // Board size at the start of the run and the inputs made since, for replays
+    start_size: (u16, u16),
+    inputs: Vec<(u64, ReplayInput)>,
+    // Seed of the current run; the RNG is re-seeded from it on reset
+    seed: u64,
+    rng: StdRng,
+    enemy_color: Color,
+}
+
+impl GameState {
+    // Initialize new game
+    fn new(width: u16, height: u16, cfg: &GameConfig) -> Self {
+        let mut gs = GameState {
+            width,
+            height,
+            ships: Ship::fleet(width, height, cfg),
+            bullets: Vec::new(),
+            boss: None,
+            shields: Vec::new(),
+            enemy_bullets: Vec::new(),
+            enemies: Vec::new(),
+            blocked_shot: 0,
+            last_shot_tick: None,
+            beam_charge: None,
+            beam_cooldown: 0,
+            beam: None,
+            ammo_in_mag: cfg.magazine_size,
+            reload_ticks: 0,
+            bombs: cfg.smart_bombs,
+            ship_speed: cfg.player_step,
+            trainer: None,
+            hitstop: 0,
+            enemy_panic: 0,
+            wave_modifier: None,
+            formation: Formation::Grid,
+            sudden_death: false,
+            enemy_targets: Vec::new(),
+            spawn_anim: 0,
+            awaiting_ready: false,
+            wave_clear_anim: 0,
+            wave_accuracy: None,
+            swarm: Vec::new(),
+            ufo: None,
+            powerups: Vec::new(),
+            buffs: Vec::new(),
+            obstacles: Vec::new(),
+            events: Vec::new(),
+            sparks: Vec::new(),
+            score: 0,
+            kills: 0,
+            wave_kills: 0,
+            wave_start_tick: 0,
+            wave_shots: 0,
+            wave_hits: 0,
+            wave_bounces: 0,
+            descent_pause: 0,
+            descent_anim: 0,
+            descent_drop: 0,
+            wrap_shift: 0,
+            weapon_level: 1,
+            tick_count: 0,
+            turbo_ticks: 0,
+            elapsed_ms: 0,
+            live_ms: 0,
+            time_bonus_ms: 0,
+            time_up: false,
+            enemy_tick_acc: 0,
+            enemy_move_every_ticks: cfg.enemy_move_every_ticks,
+            enemy_direction: 1,
+            game_over: false,
+            game_over_anim: 0,
+            victory_anim: 0,
+            fireworks: Vec::new(),
+            victory: false,
+            spawn_rows: cfg.initial_enemy_rows,
+            spawn_cols: cfg.initial_enemy_cols,
+            level: 1,
+            fire_cursor: 0,
+            start_level: 1,
+            difficulty: Difficulty::Normal,
+            challenges: Vec::new(),
+            assisted: cfg.aim_assist,
+            versus: cfg.versus,
+            wrap_ship: cfg.wrap_ship,
+            versus_reload: 0,
+            run_recorded: false,
+            combo: 0,
+            combo_ticks: 0,
+            best_combo: 0,
+            shots_fired: 0,
+            shots_hit: 0,
+            waves_cleared: 0,
+            new_best: false,
+            score_samples: Vec::new(),
+            start_size: (width, height),
+            inputs: Vec::new(),
+            seed: cfg.seed,
+            rng: StdRng::seed_from_u64(cfg.seed),
+            enemy_color: Color::Red,
+        };
+        gs.spawn_shields(cfg);
+        gs.spawn_enemies(cfg);
+        gs
+    }
+
+    // Generate a grid of enemies at the top
+    fn spawn_enemies(&mut self, cfg: &GameConfig) {
+        self.enemies.clear();
+        self.boss = None;
+        self.wave_kills = 0;
+        self.wave_start_tick = self.tick_count;
+        self.wave_shots = 0;
+        self.wave_hits = 0;
+        self.wave_bounces = 0;
+        self.descent_pause = 0;
+        self.descent_anim = 0;
+        self.wrap_shift = 0;
+        // Picked from a stream of their own, so turning colors on doesn't change how a seed plays
+        if cfg.randomize_enemy_colors {
+            let mut rng = StdRng::seed_from_u64(self.seed ^ self.level as u64);
+            self.enemy_color = *ENEMY_PALETTE.choose(&mut rng).unwrap();
+        }
+        if let Some(row) = cfg.level_row(self.level) {
+            self.enemy_move_every_ticks = row.move_every_ticks;
+        }
+        let campaign = cfg.campaign_level(self.level);
+        if let Some(ticks) = campaign.and_then(|l| l.move_every_ticks) {
+            self.enemy_move_every_ticks = ticks;
+        }
+        // The finale runs at top speed and plays it straight, without a modifier or
+        // bunkers to hide behind
+        if self.sudden_death {
+            self.enemy_move_every_ticks = 1;
+            self.shields.clear();
+            self.revert_wave_modifier();
+            self.log_event("!!! SUDDEN DEATH !!!");
+        } else {
+            self.roll_wave_modifier(cfg);
+        }
+        let left_margin = 2;
+        let right_margin = 2;
+        let usable_w = self.width.saturating_sub(left_margin + right_margin);
+        let cols = self.spawn_cols as u16;
+        let spacing_x = (usable_w / (cols + 1)).max(1);
+        // Keep the formation in the top half so a wave never spawns on top of the player
+        let max_rows = ((self.height / 2).saturating_sub(2) / 2).max(1);
+        let rows = (self.spawn_rows as u16).min(max_rows);
+
+        if campaign.is_none() && self.boss_wave(cfg) {
+            let max_hp = cfg.boss_hp.max(1) * (self.level / cfg.boss_every) as u32;
+            self.boss = Some(Boss {
+                pos: Pos {
+                    x: (self.width.saturating_sub(BOSS_WIDTH) / 2).max(2),
+                    y: 2,
+                },
+                hp: max_hp,
+                max_hp,
+                direction: 1,
+            });
+            self.log_event(format!("Wave {}: BOSS", self.level));
+        } else if let Some(level) = campaign {
+            // A campaign wave keeps its drawn layout, clipped to the board's top half
+            if !level.name.is_empty() {
+                self.log_event(format!("Wave {}: {}", self.level, level.name));
+            }
+            for cell in &level.formation {
+                let (x, y) = (left_margin + cell.x, 2 + cell.y);
+                if x < self.width - 2 && y < (self.height / 2).max(3) {
+                    self.enemies
+                        .push(Enemy::new(Pos { x, y }, EnemyKind::for_row(cell.y)));
+                }
+            }
+        } else {
+            self.formation = cfg
+                .formations
+                .get((self.level - 1) % cfg.formations.len().max(1))
+                .copied()
+                .unwrap_or(Formation::Grid);
+            if self.formation != Formation::Grid {
+                self.log_event(format!(
+                    "Wave {}: {} formation",
+                    self.level,
+                    self.formation.label()
+                ));
+            }
+            for row in 0..rows {
+                for col in 0..cols {
+                    if !self.formation.contains(row, col, rows, cols) {
+                        continue;
+                    }
+                    let x = left_margin + spacing_x * (col + 1);
+                    let y = 2 + row * 2;
+                    if x < self.width - 1 && y < self.height - 2 {
+                        self.enemies
+                            .push(Enemy::new(Pos { x, y }, EnemyKind::for_row(row)));
+                    }
+                }
+            }
+        }
+
+        // Top up a sparse wave from the free cells of the formation rows; a boss
+        // fights alone
+        let missing = cfg.min_wave_enemies.saturating_sub(self.enemies.len());
+        if missing > 0 && self.boss.is_none() {
+            let free: Vec<Pos> = (0..max_rows)
+                .map(|row| 2 + row * 2)
+                .filter(|&y| y < self.height - 2)
+                .flat_map(|y| (2..self.width.saturating_sub(2)).map(move |x| Pos { x, y }))
+                .filter(|p| !self.enemies.iter().any(|e| e.pos == *p))
+                .collect();
+            let extra: Vec<Enemy> = free
+                .choose_multiple(&mut self.rng, missing)
+                .map(|&pos| Enemy::new(pos, EnemyKind::for_row((pos.y - 2) / 2)))
+                .collect();
+            self.enemies.extend(extra);
+        }
+
+        if !self.sudden_death {
+            self.regen_shields(cfg);
+        }
+
+        // Start the wave above the board and let it slide down into place
+        self.enemy_targets = self.enemies.clone();
+        self.spawn_anim = cfg.spawn_anim_ticks;
+        self.awaiting_ready = cfg.wave_intro_wait_for_key;
+        self.place_spawning_enemies(cfg);
+
+        // Opening volley: shots fanning out from each ship's column, already on their
+        // way. Every bullet is a trigger pull, so heat and the magazine cut it short.
+        let ships: Vec<usize> = (0..self.ships.len())
+            .filter(|&i| self.ships[i].in_play())
+            .collect();
+        if ships.is_empty() {
+            return;
+        }
+        for n in 0..cfg.opening_volley {
+            let i = ships[n % ships.len()];
+            let ship = self.ships[i];
+            if ship.overheated || self.magazine_empty(cfg) {
+                continue;
+            }
+            // 0, -2, 2, -4, 4, ...
+            let k = (n / ships.len()) as i16;
+            let offset = if k % 2 == 1 { -k - 1 } else { k };
+            let x = (ship.pos.x as i16 + offset).clamp(1, self.width as i16 - 2) as u16;
+            self.add_heat(cfg, i);
+            self.bullets.push(Bullet {
+                pos: Pos {
+                    x,
+                    y: ship.pos.y.saturating_sub(1),
+                },
+                tier: self.weapon_level,
+                dx: 0,
+                hit: false,
+                pierce: cfg.pierce_hits,
+                owner: i,
+            });
+            self.shots_fired += 1;
+            self.wave_shots += 1;
+            self.spend_round(cfg);
+        }
+    }
+
+    // Cells of intact bunkers, evenly spaced across the board, as long as they fit
+    // below the formation's half of the board
+    fn shield_layout(&self, cfg: &GameConfig) -> Vec<Pos> {
+        let mut cells = Vec::new();
+        let bottom = self.ship_row().saturating_sub(2);
+        let top = bottom.saturating_sub(SHIELD_SHAPE.len() as u16 - 1);
+        let shape_w = SHIELD_SHAPE[0].len() as u16;
+        if cfg.shields == 0 || top <= self.height / 2 {
+            return cells;
+        }
+        let n = cfg.shields as u16;
+        for i in 1..=n {
+            let left = (self.width * i / (n + 1)).saturating_sub(shape_w / 2);
+            for (dy, row) in SHIELD_SHAPE.iter().enumerate() {
+                for (dx, c) in row.chars().enumerate() {
+                    let pos = Pos {
+                        x: left + dx as u16,
+                        y: top + dy as u16,
+                    };
+                    let inside = pos.x >= 1 && pos.x <= self.width.saturating_sub(2);
+                    if c == '#' && inside && !cells.contains(&pos) {
+                        cells.push(pos);
+                    }
+                }
+            }
+        }
+        cells
+    }
+
+    // Build fresh bunkers for a new run
+    fn spawn_shields(&mut self, cfg: &GameConfig) {
+        self.shields = self
+            .shield_layout(cfg)
+            .into_iter()
+            .map(|pos| ShieldCell { pos, hp: SHIELD_HP })
+            .collect();
+    }
+
+    // Repair up to `shield_regen` bunker cells: damaged ones heal to full and
+    // destroyed ones grow back, but never beyond the intact bunkers
+    fn regen_shields(&mut self, cfg: &GameConfig) {
+        let mut budget = cfg.shield_regen;
+        for pos in self.shield_layout(cfg) {
+            if budget == 0 {
+                break;
+            }
+            match self.shields.iter_mut().find(|s| s.pos == pos) {
+                Some(cell) if cell.hp == SHIELD_HP => continue,
+                Some(cell) => cell.hp = SHIELD_HP,
+                None => self.shields.push(ShieldCell { pos, hp: SHIELD_HP }),
+            }
+            budget -= 1;
+        }
+    }
+
+    // Bullets chip the shield cell they reach and are stopped by it (the player's
+    // only if configured so); enemies crush any cell they move onto
+    fn erode_shields(&mut self, cfg: &GameConfig) {
+        if self.shields.is_empty() {
+            return;
+        }
+        let shields = &mut self.shields;
+        let mut absorb = |pos: Pos| match shields.iter_mut().find(|s| s.pos == pos && s.hp > 0) {
+            Some(cell) => {
+                cell.hp -= 1;
+                true
+            }
+            None => false,
+        };
+        if cfg.shields_absorb_player_shots {
+            self.bullets.retain(|b| !absorb(b.pos));
+        }
+        self.enemy_bullets.retain(|b| !absorb(b.pos));
+        let enemies = &self.enemies;
+        self.shields
+            .retain(|s| s.hp > 0 && !enemies.iter().any(|e| e.pos == s.pos));
+    }
+
+    // Whether the current level is a boss wave; the sudden-death finale never is
+    fn boss_wave(&self, cfg: &GameConfig) -> bool {
+        cfg.boss_every > 0
+            && self.level.is_multiple_of(cfg.boss_every)
+            && !self.sudden_death
+            && self.width >= BOSS_WIDTH + 4
+    }
+
+    // Sweep the boss a cell sideways, dipping a row at one wall and rising at the other
+    fn sweep_boss(&mut self) {
+        let max_x = self.width.saturating_sub(BOSS_WIDTH + 2);
+        let Some(boss) = &mut self.boss else {
+            return;
+        };
+        let x = boss.pos.x as i16 + boss.direction as i16;
+        if x < 2 || x > max_x as i16 {
+            boss.direction *= -1;
+            boss.pos.y = if boss.direction < 0 { 3 } else { 2 };
+        } else {
+            boss.pos.x = x as u16;
+        }
+    }
+
+    // Position dropping-in enemies by how far along the spawn animation is
+    fn place_spawning_enemies(&mut self, cfg: &GameConfig) {
+        let total = cfg.spawn_anim_ticks.max(1);
+        for (e, t) in self.enemies.iter_mut().zip(&self.enemy_targets) {
+            e.pos.y = t.pos.y - t.pos.y * self.spawn_anim / total;
+        }
+    }
+
+    // Snap the wave onto its grid spots
+    fn finish_spawn_anim(&mut self) {
+        self.awaiting_ready = false;
+        if self.spawn_anim > 0 {
+            self.spawn_anim = 0;
+            self.enemies = self.enemy_targets.clone();
+        }
+    }
+
+    // Apply one level of difficulty progression (without spawning)
+    fn level_up(&mut self) {
+        self.level += 1;
+        if self.level.is_multiple_of(2) {
+            self.spawn_rows = (self.spawn_rows + 1).min(6);
+        } else {
+            self.spawn_cols = (self.spawn_cols + 1).min(12);
+        }
+        self.enemy_move_every_ticks = self.enemy_move_every_ticks.saturating_sub(1).max(1);
+    }
+
+    // Reset state for restart, beginning at the run's start level
+    fn reset(&mut self, cfg: &GameConfig) {
+        self.ships = Ship::fleet(self.width, self.height, cfg);
+        self.assisted = cfg.aim_assist;
+        self.versus = cfg.versus;
+        self.wrap_ship = cfg.wrap_ship;
+        self.versus_reload = 0;
+        self.bullets.clear();
+        self.enemy_bullets.clear();
+        self.enemies.clear();
+        self.boss = None;
+        self.shields.clear();
+        self.swarm.clear();
+        self.ufo = None;
+        self.powerups.clear();
+        self.buffs.clear();
+        self.obstacles.clear();
+        self.events.clear();
+        self.sparks.clear();
+        self.trainer = None;
+        self.blocked_shot = 0;
+        self.last_shot_tick = None;
+        self.beam_charge = None;
+        self.beam_cooldown = 0;
+        self.beam = None;
+        self.ammo_in_mag = cfg.magazine_size;
+        self.reload_ticks = 0;
+        self.bombs = cfg.smart_bombs;
+        self.hitstop = 0;
+        self.enemy_panic = 0;
+        self.score = 0;
+        self.kills = 0;
+        self.wave_kills = 0;
+        self.wave_start_tick = 0;
+        self.wave_shots = 0;
+        self.wave_hits = 0;
+        self.wave_clear_anim = 0;
+        self.wave_accuracy = None;
+        self.wave_bounces = 0;
+        self.descent_pause = 0;
+        self.descent_anim = 0;
+        self.wrap_shift = 0;
+        self.weapon_level = 1;
+        self.tick_count = 0;
+        self.turbo_ticks = 0;
+        self.elapsed_ms = 0;
+        self.live_ms = 0;
+        self.time_bonus_ms = 0;
+        self.time_up = false;
+        self.enemy_tick_acc = 0;
+        self.enemy_move_every_ticks = cfg.enemy_move_every_ticks;
+        self.enemy_direction = 1;
+        self.game_over = false;
+        self.game_over_anim = 0;
+        self.victory_anim = 0;
+        self.fireworks.clear();
+        self.sudden_death = false;
+        self.victory = false;
+        self.spawn_rows = cfg.initial_enemy_rows;
+        self.spawn_cols = cfg.initial_enemy_cols;
+        self.level = 1;
+        self.fire_cursor = 0;
+        self.run_recorded = false;
+        self.combo = 0;
+        self.combo_ticks = 0;
+        self.best_combo = 0;
+        self.shots_fired = 0;
+        self.shots_hit = 0;
+        self.waves_cleared = 0;
+        self.new_best = false;
+        self.score_samples.clear();
+        self.start_size = (self.width, self.height);
+        self.inputs.clear();
+        self.rng = StdRng::seed_from_u64(self.seed);
+        while self.level < self.start_level {
+            self.level_up();
+        }
+        // Starting further in comes with the speed those levels would have earned
+        self.ship_speed = cfg.player_step;
+        for _ in 0..(self.level - 1)
+            .checked_div(cfg.speed_up_every)
+            .unwrap_or(0)
+        {
+            self.speed_up(cfg);
+        }
+        self.enemy_color = Color::Red;
+        self.spawn_shields(cfg);
+        self.spawn_enemies(cfg);
+    }
+
+    // End the run, leaving the invaders a few ticks to finish their descent
+    fn trigger_game_over(&mut self, cfg: &GameConfig) {
+        self.game_over = true;
+        self.game_over_anim = cfg.game_over_anim_ticks;
+    }
+
+    // Time left in a time-attack run, budget plus bonuses minus play time
+    fn time_left_ms(&self, cfg: &GameConfig) -> Option<u64> {
+        if !cfg.time_attack || self.trainer.is_some() {
+            return None;
+        }
+        let budget = cfg.time_attack_secs * 1000 + self.time_bonus_ms;
+        Some(budget.saturating_sub(self.elapsed_ms))
+    }
+
+    // Win the run, celebrating with fireworks before the victory screen
+    fn win(&mut self, cfg: &GameConfig) {
+        self.victory = true;
+        self.victory_anim = cfg.victory_anim_ticks;
+    }
+
+    // Advance the fireworks: launch rockets while there's time for them to burst,
+    // move every particle, and burst rockets whose fuse has run out
+    fn tick_fireworks(&mut self) {
+        self.victory_anim -= 1;
+        if self.victory_anim > FIREWORK_FUSE_TICKS as u64
+            && self.victory_anim.is_multiple_of(FIREWORK_LAUNCH_EVERY)
+            && self.width > 6
+        {
+            let rocket = Firework {
+                x: self.rng.gen_range(3..self.width - 3) as f32,
+                y: self.ship_row() as f32,
+                vx: self.rng.gen_range(-0.3..0.3),
+                vy: -1.0,
+                color: *FIREWORK_COLORS.choose(&mut self.rng).unwrap(),
+                rocket: true,
+            };
+            let fuse = self
+                .rng
+                .gen_range(FIREWORK_FUSE_TICKS / 2..=FIREWORK_FUSE_TICKS);
+            self.fireworks.insert(0, (rocket, fuse));
+        }
+        let mut bursts = Vec::new();
+        for (p, ttl) in &mut self.fireworks {
+            p.x += p.vx;
+            p.y += p.vy;
+            if p.rocket && *ttl == 1 {
+                bursts.push(*p);
+            } else if !p.rocket {
+                // Sparks drift down as they fade
+                p.vy += 0.1;
+            }
+        }
+        prune_ttl(&mut self.fireworks, FIREWORK_CAP);
+        for rocket in bursts {
+            for i in 0..8 {
+                let angle = i as f32 * std::f32::consts::FRAC_PI_4;
+                let spark = Firework {
+                    vx: angle.cos(),
+                    vy: angle.sin() * 0.5,
+                    rocket: false,
+                    ..rocket
+                };
+                self.fireworks.insert(0, (spark, FIREWORK_SPARK_TICKS));
+            }
+        }
+        self.fireworks.truncate(FIREWORK_CAP);
+    }
+
+    // Real time between ticks; turbo halves it so enemies speed up along with everything else,
+    // though never below MIN_TICK_MS
+    fn tick_rate(&self, cfg: &GameConfig) -> Duration {
+        if self.turbo_ticks > 0 {
+            Duration::from_millis((cfg.tick_ms / 2).max(MIN_TICK_MS))
+        } else {
+            Duration::from_millis(cfg.tick_ms)
+        }
+    }
+
+    fn run_tag(&self) -> String {
+        if self.trainer.is_some() {
+            return "trainer".to_string();
+        }
+        run_tag(
+            self.start_level,
+            self.difficulty,
+            &self.challenges,
+            self.mode_tag(),
+            self.assisted,
+        )
+    }
+
+    // The run's result as a JSON object, for score submission
+    fn result_json(&self) -> String {
+        let mut tags: Vec<String> = self
+            .challenges
+            .iter()
+            .map(|c| format!("\"{}\"", c.tag()))
+            .collect();
+        if self.difficulty != Difficulty::Normal {
+            tags.insert(0, format!("\"{}\"", self.difficulty.tag()));
+        }
+        if let Some(mode) = self.mode_tag() {
+            tags.push(format!("\"{mode}\""));
+        }
+        if self.assisted {
+            tags.push("\"assist\"".to_string());
+        }
+        let date = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_secs());
+        format!(
+            "{{\"score\":{},\"level\":{},\"seed\":{},\"modifiers\":[{}],\"date\":{}}}",
+            self.score,
+            self.level,
+            self.seed,
+            tags.join(","),
+            date
+        )
+    }
+
+    // Destroy formation enemies sharing a cell with a player bullet
+    fn resolve_hits(&mut self, cfg: &GameConfig) {
+        let mut to_remove = Vec::new();
+        let mut spent = Vec::new();
+        let mut awarded = Vec::new();
+        for bi in 0..self.bullets.len() {
+            let b = self.bullets[bi];
+            if let Some(ei) = self.enemies.iter().position(|e| e.pos == b.pos) {
+                to_remove.push(ei);
+                if !b.hit {
+                    self.wave_hits += 1;
+                    self.shots_hit += 1;
+                }
+                self.bullets[bi].hit = true;
+                self.extend_combo(cfg);
+                let points = self.kill_points(cfg)
+                    * self.enemies[ei].kind.points()
+                    * self.combo_multiplier();
+                awarded.push(points);
+                self.award(b.owner, points);
+                self.kills += 1;
+                self.wave_kills += 1;
+                self.enemy_panic = cfg.enemy_panic_ticks;
+                if cfg.bullets_blocked_by_enemies {
+                    let b = &mut self.bullets[bi];
+                    b.pierce = b.pierce.saturating_sub(1);
+                    if b.pierce == 0 {
+                        spent.push(bi);
+                    }
+                }
+            }
+        }
+        for points in awarded {
+            self.log_event(format!("Enemy destroyed +{points}"));
+        }
+        // Spent bullets stop at the enemy that used them up and can't reach the ones behind it
+        for bi in spent.iter().rev() {
+            self.bullets.remove(*bi);
+        }
+        to_remove.sort_unstable();
+        to_remove.dedup();
+        for idx in &to_remove {
+            let pos = self.enemies[*idx].pos;
+            self.drop_powerup(cfg, pos);
+        }
+
+        for idx in to_remove.iter().rev() {
+            if *idx < self.enemies.len() {
+                self.enemies.remove(*idx);
+            }
+        }
+
+        // Every bullet reaching the boss is spent on it
+        let Some(mut boss) = self.boss else {
+            return;
+        };
+        let mut shooters = Vec::new();
+        let mut fresh = 0;
+        self.bullets.retain(|b| {
+            let hit = boss.covers(b.pos);
+            if hit {
+                shooters.push(b.owner);
+                fresh += usize::from(!b.hit);
+            }
+            !hit
+        });
+        self.wave_hits += fresh;
+        self.shots_hit += fresh;
+        let Some(&last) = shooters.last() else {
+            return;
+        };
+        boss.hp = boss.hp.saturating_sub(shooters.len() as u32);
+        for owner in shooters {
+            self.award(owner, cfg.kill_points);
+        }
+        if boss.hp > 0 {
+            self.boss = Some(boss);
+            return;
+        }
+        self.boss = None;
+        self.kills += 1;
+        self.wave_kills += 1;
+        let points = cfg.kill_points * BOSS_KILL_MULTIPLIER;
+        self.award(last, points);
+        self.log_event(format!("Boss destroyed +{points}"));
+    }
+
+    // Credit points to the run and to the ship whose shot earned them
+    fn award(&mut self, owner: usize, points: usize) {
+        self.score += points;
+        if let Some(ship) = self.ships.get_mut(owner) {
+            ship.score += points;
+        }
+    }
+
+    // Two-player mode of the run, if any
+    fn mode_tag(&self) -> Option<&'static str> {
+        if self.versus {
+            Some("versus")
+        } else if self.ships.len() > 1 {
+            Some("coop")
+        } else {
+            None
+        }
+    }
+
+    // Row the ships sit on
+    fn ship_row(&self) -> u16 {
+        self.ships[0].pos.y
+    }
+
+    // Ship the invaders go after from column `x`: the nearest one still in play
+    fn target(&self, x: u16) -> Pos {
+        self.ships
+            .iter()
+            .filter(|s| s.in_play())
+            .min_by_key(|s| s.pos.x.abs_diff(x))
+            .map_or(self.ships[0].pos, |s| s.pos)
+    }
+
+    // Ships still in play that are touching `pos`
+    fn ships_at(&self, pos: Pos) -> Vec<usize> {
+        (0..self.ships.len())
+            .filter(|&i| self.ships[i].in_play() && self.ships[i].pos == pos)
+            .collect()
+    }
+
+    // The run is over once no ship has lives left
+    fn all_out(&self) -> bool {
+        !self.ships.iter().any(Ship::in_play)
+    }
+
+    // Share of fired shots that hit something, as a whole percentage
+    fn accuracy(&self) -> usize {
+        (self.shots_hit * 100)
+            .checked_div(self.shots_fired)
+            .unwrap_or(0)
+    }
+
+    // One-line summary of the run for scripts. The format is stable: space-separated
+    // key=value fields in this order, with new fields only ever appended:
+    //   space-invaders score=<n> level=<n> kills=<n> accuracy=<percent> duration_ms=<n> seed=<n> outcome=<o>
+    // where the outcome is won, lost, quit (left mid-run) or none (no run was played)
+    fn summary_line(&self) -> String {
+        let outcome = if self.victory {
+            "won"
+        } else if self.game_over {
+            "lost"
+        } else if self.tick_count > 0 {
+            "quit"
+        } else {
+            "none"
+        };
+        format!(
+            "space-invaders score={} level={} kills={} accuracy={} duration_ms={} seed={} outcome={}",
+            self.score,
+            self.level,
+            self.kills,
+            self.accuracy(),
+            self.elapsed_ms,
+            self.seed,
+            outcome
+        )
+    }
+
+    // Whether the end-of-game overlay should be shown
+    fn ended(&self) -> bool {
+        (self.game_over && self.game_over_anim == 0) || (self.victory && self.victory_anim == 0)
+    }
+
+    // Update all entities and handle game logic each tick. Within a tick the order is fixed:
+    // player bullets move and hit, then a released beam fires, then the formation moves and is hit again by any bullet
+    // it stepped onto, then divers move and are hit likewise, then the wave-clear check,
+    // then enemy fire, the swarm, asteroids, and last the breach check. An enemy shot on the tick it would reach the player's row is therefore
+    // destroyed before it can breach, and no enemy can step past a bullet unharmed.
+    fn tick(&mut self, cfg: &GameConfig) {
+        // A landed wave waiting for the player holds everything still, play time included
+        if self.awaiting_ready && self.spawn_anim == 0 {
+            return;
+        }
+        // So does the wave-complete interlude, which then sends in the next wave
+        if self.wave_clear_anim > 0 {
+            self.wave_clear_anim -= 1;
+            if self.wave_clear_anim == 0 {
+                self.next_wave(cfg);
+            }
+            return;
+        }
+        // Play time stops once the run has ended, so the end screen isn't counted
+        if !self.game_over && !self.victory {
+            self.elapsed_ms += cfg.tick_ms;
+        }
+        self.turbo_ticks = self.turbo_ticks.saturating_sub(1);
+        self.blocked_shot = self.blocked_shot.saturating_sub(1);
+        for ship in &mut self.ships {
+            ship.invulnerable = ship.invulnerable.saturating_sub(1);
+            ship.heat = ship.heat.saturating_sub(cfg.heat_cooling);
+            if ship.heat == 0 {
+                ship.overheated = false;
+            }
+        }
+        self.beam_cooldown = self.beam_cooldown.saturating_sub(1);
+        self.beam = self
+            .beam
+            .filter(|(_, ticks)| *ticks > 1)
+            .map(|(x, ticks)| (x, ticks - 1));
+        if self.reload_ticks > 0 {
+            self.reload_ticks -= 1;
+            if self.reload_ticks == 0 {
+                self.ammo_in_mag = cfg.magazine_size;
+            }
+        }
+        self.prune_effects();
+
+        // Game-over animation: enemies keep descending, nothing else happens
+        if self.game_over_anim > 0 {
+            self.game_over_anim -= 1;
+            let floor = self.ship_row();
+            for e in &mut self.enemies {
+                if e.pos.y < floor {
+                    e.pos.y += 1;
+                }
+            }
+            return;
+        }
+        if self.victory_anim > 0 {
+            self.tick_fireworks();
+            return;
+        }
+        if self.game_over || self.victory {
+            return;
+        }
+        if self.time_left_ms(cfg) == Some(0) {
+            self.time_up = true;
+            self.log_event("Time up!");
+            self.trigger_game_over(cfg);
+            return;
+        }
+        // Hit-stop: nothing moves, and the tick counter (which drives every
+        // game timer) holds still so cadences resume exactly where they were
+        if self.hitstop > 0 {
+            self.hitstop -= 1;
+            return;
+        }
+
+        self.tick_count += 1;
+        self.enemy_tick_acc += 1;
+        self.enemy_panic = self.enemy_panic.saturating_sub(1);
+        if self.elapsed_ms / 1000 > self.score_samples.len() as u64 {
+            self.score_samples.push(self.score);
+        }
+        if self.trainer.is_some() {
+            self.tick_trainer(cfg);
+            return;
+        }
+
+        // Wave intro: the formation drops in and can't be hit, move or fire until it lands
+        let landing = self.spawn_anim > 0;
+        if landing {
+            self.spawn_anim -= 1;
+            self.place_spawning_enemies(cfg);
+            self.enemy_tick_acc = 0;
+        }
+
+        // A combo lapses when its window runs out
+        if self.combo_ticks > 0 {
+            self.combo_ticks -= 1;
+            if self.combo_ticks == 0 {
+                self.combo = 0;
+            }
+        }
+
+        self.move_bullets(cfg);
+        self.erode_shields(cfg);
+
+        if !landing {
+            self.resolve_hits(cfg);
+            self.tick_beam(cfg);
+        }
+
+        // Move enemies horizontally and down
+        self.descent_anim = self.descent_anim.saturating_sub(1);
+        // After a descent the formation holds for a beat, then resumes its usual cadence
+        if self.descent_pause > 0 {
+            self.descent_pause -= 1;
+            self.enemy_tick_acc = 0;
+        } else if !landing && self.enemy_tick_acc >= self.move_interval() {
+            self.enemy_tick_acc = 0;
+            if self.boss.is_some() {
+                self.sweep_boss();
+            } else if cfg.wrap_enemies {
+                self.wrap_formation(cfg);
+            } else {
+                self.bounce_formation(cfg);
+            }
+            self.resolve_hits(cfg);
+            self.erode_shields(cfg);
+        }
+        if !landing {
+            self.tick_divers(cfg);
+            self.resolve_hits(cfg);
+        }
+
+        // Permanent weapon upgrades at score milestones
+        let weapon_level = 1 + cfg
+            .weapon_upgrade_scores
+            .iter()
+            .filter(|&&threshold| self.score >= threshold)
+            .count();
+        if weapon_level > self.weapon_level {
+            self.log_event("Weapon upgraded!");
+        }
+        self.weapon_level = weapon_level;
+
+        // Level up once every hostile is gone, swarm included; clearing the last level wins
+        if self.enemies.is_empty() && self.swarm.is_empty() && self.boss.is_none() {
+            self.log_event(format!("Wave {} cleared", self.level));
+            self.waves_cleared += 1;
+            self.wave_accuracy = (self.wave_hits * 100)
+                .checked_div(self.wave_shots)
+                .map(|pct| {
+                    let pct = pct.min(100);
+                    (pct, cfg.accuracy_bonus * pct / 100 / 10 * 10)
+                });
+            if let Some((pct, bonus)) = self.wave_accuracy.filter(|(_, bonus)| *bonus > 0) {
+                self.score += bonus;
+                self.log_event(format!("Accuracy {pct}% +{bonus}"));
+            }
+            if cfg.time_attack {
+                let wave_secs = (self.tick_count - self.wave_start_tick) * cfg.tick_ms / 1000;
+                let bonus = TIME_ATTACK_PAR_SECS.saturating_sub(wave_secs) / 2;
+                if bonus > 0 {
+                    self.time_bonus_ms += bonus * 1000;
+                    self.log_event(format!("Quick clear +{bonus}s"));
+                }
+            }
+            // Surviving the finale wins outright
+            if self.sudden_death {
+                self.score += cfg.sudden_death_bonus;
+                self.log_event(format!("Survived! +{}", cfg.sudden_death_bonus));
+                self.win(cfg);
+                return;
+            }
+            if !self.finale_next(cfg)
+                && !cfg.endless
+                && cfg.last_level().is_some_and(|max| self.level >= max)
+            {
+                self.win(cfg);
+                return;
+            }
+            // Hold for the wave-complete interlude, or without one land the
+            // wave-clearing kill with a brief freeze
+            if cfg.wave_clear_ticks > 0 {
+                self.wave_clear_anim = cfg.wave_clear_ticks;
+            } else {
+                self.hitstop = cfg.hitstop_ticks;
+                self.next_wave(cfg);
+            }
+        }
+
+        // Enemy return fire
+        self.move_enemy_bullets(cfg);
+        self.erode_shields(cfg);
+        if cfg.bullets_cancel {
+            self.cancel_bullets();
+        }
+        let fire_every = self.fire_interval(cfg);
+        // A wave spawned by this tick's clear is already dropping in and holds its fire
+        let quiet = landing || self.spawn_anim > 0;
+        if !quiet && !self.versus && fire_every > 0 && self.tick_count.is_multiple_of(fire_every) {
+            self.enemy_fire(cfg);
+        }
+        self.versus_reload = self.versus_reload.saturating_sub(1);
+        // Shots pass through a ship that is still invulnerable
+        for i in 0..self.ships.len() {
+            let ship = self.ships[i];
+            if !ship.in_play() || ship.invulnerable > 0 {
+                continue;
+            }
+            let before = self.enemy_bullets.len();
+            self.enemy_bullets.retain(|b| b.pos != ship.pos);
+            if self.enemy_bullets.len() < before {
+                self.hit_ship(cfg, i);
+            }
+        }
+
+        // Swarm enemies, the UFO, power-ups and asteroids move independently of the formation
+        self.tick_swarm(cfg);
+        self.tick_ufo(cfg);
+        self.tick_powerups(cfg);
+        self.tick_obstacles(cfg);
+
+        // Check if enemies reached bottom, past any ship still in play
+        let ships: Vec<Pos> = self
+            .ships
+            .iter()
+            .filter(|s| s.in_play())
+            .map(|s| s.pos)
+            .collect();
+        let breaching = |e: &Enemy| ships.iter().any(|&ship| cfg.breaches(e.slot(), ship));
+        match cfg.enemy_breach_penalty {
+            BreachPenalty::GameOver => {
+                if self.enemies.iter().any(breaching) {
+                    self.trigger_game_over(cfg);
+                }
+            }
+            penalty => {
+                // Each breach costs a set number of points, or else a life
+                let points = match penalty {
+                    BreachPenalty::Points(p) => Some(p),
+                    _ => None,
+                };
+                let before = self.enemies.len();
+                self.enemies.retain(|e| !breaching(e));
+                let breached = before - self.enemies.len();
+
+                for _ in 0..breached {
+                    match points {
+                        Some(p) => {
+                            self.score = self.score.saturating_sub(p);
+                            self.log_event(format!("Breach! -{p}"));
+                        }
+                        // A breach costs the first ship still in play a life
+                        None => {
+                            let i = self.ships.iter().position(Ship::in_play).unwrap_or(0);
+                            self.lose_life(cfg, i, "Breach! -1 life")
+                        }
+                    }
+                }
+
+                if self.all_out() {
+                    self.trigger_game_over(cfg);
+                } else if breached > 0 && self.enemies.is_empty() {
+                    // The whole wave slipped through: send it again
+                    self.spawn_enemies(cfg);
+                }
+            }
+        }
+    }
+
+    // Whether the wave after the one just cleared is the sudden-death finale
+    fn finale_next(&self, cfg: &GameConfig) -> bool {
+        cfg.sudden_death
+            .is_some_and(|trigger| trigger.reached(self.level, self.score))
+    }
+
+    // Move on to the next level and send in its wave; a bomb comes back with it
+    fn next_wave(&mut self, cfg: &GameConfig) {
+        let finale = self.finale_next(cfg);
+        self.level_up();
+        self.bombs = (self.bombs + 1).min(cfg.smart_bombs);
+        if cfg.speed_up_every > 0
+            && (self.level - 1).is_multiple_of(cfg.speed_up_every)
+            && self.speed_up(cfg)
+        {
+            self.log_event("Ship speed up!");
+        }
+        self.sudden_death = finale;
+        self.spawn_enemies(cfg);
+    }
+
+    // Ticks between enemy shots; endless mode shortens it every wave down to a floor
+    fn fire_interval(&self, cfg: &GameConfig) -> u64 {
+        if self.sudden_death {
+            return MIN_ENDLESS_FIRE_TICKS;
+        }
+        let interval = self.base_fire_interval(cfg);
+        if self.wave_modifier == Some(WaveModifier::HeavyFire) {
+            interval.div_ceil(2)
+        } else {
+            interval
+        }
+    }
+
+    // Fire cadence before this wave's modifier
+    fn base_fire_interval(&self, cfg: &GameConfig) -> u64 {
+        // A campaign wave or level table sets the cadence outright
+        if let Some(ticks) = cfg
+            .campaign_level(self.level)
+            .and_then(|l| l.fire_every_ticks)
+        {
+            return ticks;
+        }
+        if let Some(row) = cfg.level_row(self.level) {
+            return row.fire_every_ticks;
+        }
+        let base = cfg.enemy_fire_every_ticks;
+        if cfg.endless && base > 0 {
+            base.saturating_sub(self.level as u64 - 1)
+                .max(MIN_ENDLESS_FIRE_TICKS.min(base))
+        } else {
+            base
+        }
+    }
+
+    // Ticks between formation steps; a panicking formation steps twice as often, down to a floor
+    fn move_interval(&self) -> u64 {
+        let base = if self.wave_modifier == Some(WaveModifier::Fast) {
+            self.enemy_move_every_ticks.div_ceil(2)
+        } else {
+            self.enemy_move_every_ticks
+        };
+        if self.enemy_panic > 0 {
+            (base / 2).max(MIN_PANIC_MOVE_TICKS.min(base))
+        } else {
+            base
+        }
+    }
+
+    // A kill extends the current chain (when combos are on)
+    fn extend_combo(&mut self, cfg: &GameConfig) {
+        if cfg.combo_window_ticks > 0 {
+            self.combo += 1;
+            self.combo_ticks = cfg.combo_window_ticks;
+            self.best_combo = self.best_combo.max(self.combo);
+        }
+    }
+
+    fn break_combo(&mut self) {
+        self.combo = 0;
+        self.combo_ticks = 0;
+    }
+
+    // Score multiplier earned by the current chain
+    fn combo_multiplier(&self) -> usize {
+        (1 + self.combo / COMBO_KILLS_PER_STEP).min(COMBO_MAX_MULTIPLIER)
+    }
+
+    // Points for the next formation kill, decayed by wave kills and wave age
+    fn kill_points(&self, cfg: &GameConfig) -> usize {
+        let by_kills = cfg.point_decay_per_wave_kill * self.wave_kills;
+        let by_age = match cfg.point_decay_every_ticks {
+            0 => 0,
+            every => ((self.tick_count - self.wave_start_tick) / every) as usize,
+        };
+        let points = cfg
+            .kill_points
+            .saturating_sub(by_kills + by_age)
+            .max(cfg.kill_points_min.min(cfg.kill_points));
+        if self.wave_modifier == Some(WaveModifier::DoublePoints) {
+            points * 2
+        } else {
+            points
+        }
+    }
+
+    // Give the new wave a random twist from the enabled set, announced in the event log.
+    // Heavy fire is only offered while enemies fire at all.
+    fn roll_wave_modifier(&mut self, cfg: &GameConfig) {
+        self.revert_wave_modifier();
+        let fires = self.fire_interval(cfg) > 0;
+        let options: Vec<WaveModifier> = cfg
+            .wave_modifiers
+            .iter()
+            .copied()
+            .filter(|m| *m != WaveModifier::HeavyFire || fires)
+            .collect();
+        if let Some(&modifier) = options.choose(&mut self.rng) {
+            self.apply_wave_modifier(modifier);
+        }
+    }
+
+    // Modifiers act through the cadence, scoring and drawing lookups, so applying or
+    // reverting one only has to set which is active
+    fn apply_wave_modifier(&mut self, modifier: WaveModifier) {
+        self.wave_modifier = Some(modifier);
+        self.log_event(format!("Wave {}: {}", self.level, modifier.label()));
+    }
+
+    fn revert_wave_modifier(&mut self) {
+        self.wave_modifier = None;
+    }
+
+    // Horizontal formation step; optionally scaled so crossing the board takes similar time at any width
+    fn enemy_step(&self, cfg: &GameConfig) -> u16 {
+        if cfg.scale_enemy_step {
+            (self.width / ENEMY_STEP_REFERENCE_WIDTH).max(1)
+        } else {
+            1
+        }
+    }
+
+    // Step the formation sideways, dropping and turning around at a wall
+    fn bounce_formation(&mut self, cfg: &GameConfig) {
+        // Room left before the formation touches the side walls (x in 2..=width-3)
+        let room = if self.enemy_direction > 0 {
+            let max_x = self.enemies.iter().map(|e| e.slot().x).max().unwrap_or(0);
+            self.width.saturating_sub(3).saturating_sub(max_x)
+        } else {
+            let min_x = self.enemies.iter().map(|e| e.slot().x).min().unwrap_or(0);
+            min_x.saturating_sub(2)
+        };
+
+        if room == 0 {
+            // move down and reverse direction
+            self.drop_formation(cfg);
+            self.enemy_direction *= -1;
+            self.wave_bounces += 1;
+        } else {
+            // Never step past the wall, even with a wide step
+            let step = self.enemy_step(cfg).min(room) as i16;
+            let shift = self.enemy_direction as i16 * step;
+            for e in &mut self.enemies {
+                let slot = e.slot_mut();
+                slot.x = (slot.x as i16 + shift) as u16;
+            }
+        }
+    }
+
+    // Shift the whole formation sideways, re-entering from the opposite wall.
+    // Every enemy moves by the same amount modulo the row, so none can overlap.
+    fn wrap_formation(&mut self, cfg: &GameConfig) {
+        let (lo, hi) = (2, self.width.saturating_sub(3));
+        if hi <= lo {
+            return;
+        }
+        let span = hi - lo + 1;
+        let step = self.enemy_step(cfg).min(span);
+        let shift = self.enemy_direction as i32 * step as i32;
+        for e in &mut self.enemies {
+            let slot = e.slot_mut();
+            let offset = slot.x.saturating_sub(lo) as i32 + shift;
+            slot.x = lo + offset.rem_euclid(span as i32) as u16;
+        }
+        self.wrap_shift += step;
+        if self.wrap_shift >= span {
+            self.wrap_shift -= span;
+            self.wave_bounces += 1;
+            if self
+                .wave_bounces
+                .is_multiple_of(cfg.wrap_descent_every.max(1))
+            {
+                self.drop_formation(cfg);
+            }
+        }
+    }
+
+    // Now and then send a column leader on a dive at the player's column, and move
+    // every diver along its loop. A diver that reaches the ship rams it and is lost.
+    fn tick_divers(&mut self, cfg: &GameConfig) {
+        let diving = self.enemies.iter().filter(|e| e.dive.is_some()).count();
+        if cfg.dive_every_ticks > 0
+            && diving < MAX_DIVERS
+            && self.tick_count.is_multiple_of(cfg.dive_every_ticks)
+        {
+            let leaders = self.column_leaders();
+            if let Some(&home) = leaders.choose(&mut self.rng) {
+                let depth = self.ship_row().saturating_sub(home.y);
+                let target_x = self.target(home.x).x;
+                if let Some(e) = self
+                    .enemies
+                    .iter_mut()
+                    .find(|e| e.pos == home && e.dive.is_none())
+                {
+                    e.dive = Some(Dive {
+                        home,
+                        target_x,
+                        t: 0,
+                        len: depth * 2 + 2,
+                    });
+                }
+            }
+        }
+        let (floor, width) = (self.ship_row(), self.width);
+        for e in &mut self.enemies {
+            let Some(dive) = &mut e.dive else {
+                continue;
+            };
+            dive.t += 1;
+            if dive.t >= dive.len {
+                e.pos = dive.home;
+                e.dive = None;
+            } else {
+                e.pos = dive.pos(floor, width);
+            }
+        }
+        let mut rammed = Vec::new();
+        for e in self.enemies.iter().filter(|e| e.dive.is_some()) {
+            rammed.extend(self.ships_at(e.pos));
+        }
+        let ships: Vec<Pos> = rammed.iter().map(|&i| self.ships[i].pos).collect();
+        self.enemies
+            .retain(|e| e.dive.is_none() || !ships.contains(&e.pos));
+        for i in rammed {
+            self.hit_ship(cfg, i);
+        }
+    }
+
+    // Move the formation down a descent, then hold it and start the drawn settle
+    fn drop_formation(&mut self, cfg: &GameConfig) {
+        let drop = self.descent(cfg);
+        for e in &mut self.enemies {
+            e.slot_mut().y += drop;
+        }
+        self.descent_pause = cfg.descent_pause_ticks;
+        self.descent_drop = drop;
+        self.descent_anim = cfg.descent_squash_ticks;
+    }
+
+    // Rows the formation is drawn above its true position while settling after
+    // a descent, shrinking to zero; hits always use the true position
+    fn descent_lag(&self, cfg: &GameConfig) -> u16 {
+        let rows = cfg.descent_squash.min(self.descent_drop) as u64;
+        let total = cfg.descent_squash_ticks.max(1);
+        (rows * self.descent_anim.min(total)).div_ceil(total) as u16
+    }
+
+    // Rows dropped on a wall bounce; grows as the wave drags on, but never
+    // carries the formation past the player's row
+    fn descent(&self, cfg: &GameConfig) -> u16 {
+        let grown = match cfg.descent_growth_every_bounces {
+            0 => 1,
+            every => (1 + self.wave_bounces / every).min(cfg.max_descent.max(1)),
+        };
+        let lowest = self.enemies.iter().map(|e| e.slot().y).max().unwrap_or(0);
+        grown.min(self.ship_row().saturating_sub(lowest)).max(1)
+    }
+
+    // Add a message to the event log, dropping the oldest beyond the cap
+    fn log_event(&mut self, msg: impl Into<String>) {
+        self.events.insert(0, (msg.into(), EVENT_TTL_TICKS));
+        self.events.truncate(EVENT_LOG_LEN);
+    }
+
+    // Age every cosmetic effect list by a tick, dropping expired entries and
+    // enforcing the caps so long sessions can't accumulate stale effects
+    fn prune_effects(&mut self) {
+        prune_ttl(&mut self.events, EVENT_LOG_LEN);
+        prune_ttl(&mut self.sparks, SPARK_CAP);
+    }
+
+    // Ship `i` was hit: lose a life, ending the game when no ship has any left.
+    // A ship that is still invulnerable shrugs it off.
+    fn hit_ship(&mut self, cfg: &GameConfig, i: usize) {
+        if self.ships[i].invulnerable > 0 {
+            return;
+        }
+        if self.buff_active(PowerUpKind::Shield) {
+            self.buffs.retain(|(k, _)| *k != PowerUpKind::Shield);
+            self.log_event("Shield absorbed the hit");
+            return;
+        }
+        self.lose_life(cfg, i, "Ship hit! -1 life");
+        if self.all_out() {
+            self.trigger_game_over(cfg);
+        }
+    }
+
+    // Take a life from ship `i` (from every ship with shared lives) and, if any are
+    // left, respawn it at its home column with the air cleared of bullets and a few
+    // ticks of invulnerability. In co-op the ship's number prefixes the message.
+    fn lose_life(&mut self, cfg: &GameConfig, i: usize, message: &str) {
+        let count = self.ships.len();
+        if count > 1 {
+            self.log_event(format!("P{}: {message}", i + 1));
+        } else {
+            self.log_event(message);
+        }
+        for (j, ship) in self.ships.iter_mut().enumerate() {
+            if j == i || cfg.shared_lives {
+                ship.lives = ship.lives.saturating_sub(1);
+            }
+        }
+        let home = ship_home_x(self.width, i, count);
+        let ship = &mut self.ships[i];
+        if ship.in_play() {
+            ship.pos.x = home;
+            ship.invulnerable = cfg.invulnerable_ticks;
+            self.bullets.clear();
+            self.enemy_bullets.clear();
+        } else if !self.all_out() {
+            self.log_event(format!("P{} is out", i + 1));
+        }
+    }
+
+    // Spawn, move and resolve collisions for swarm enemies
+    fn tick_swarm(&mut self, cfg: &GameConfig) {
+        // No reinforcements once the formation is wiped out, so the wave can end
+        if cfg.swarm_spawn_every_ticks > 0
+            && !self.enemies.is_empty()
+            && self.level >= cfg.swarm_min_level
+            && self.tick_count.is_multiple_of(cfg.swarm_spawn_every_ticks)
+            && self.width > 6
+        {
+            let x = self.rng.gen_range(2..self.width - 3);
+            self.swarm.push(Pos { x, y: 2 });
+        }
+
+        // Home loosely toward the player's column, one cell per step
+        if self
+            .tick_count
+            .is_multiple_of(cfg.swarm_move_every_ticks.max(1))
+        {
+            let targets: Vec<u16> = self.swarm.iter().map(|s| self.target(s.x).x).collect();
+            for (s, target) in self.swarm.iter_mut().zip(targets) {
+                s.y += 1;
+                if s.x < target {
+                    s.x += 1;
+                } else if s.x > target {
+                    s.x -= 1;
+                }
+            }
+        }
+
+        // One hit destroys a swarm enemy
+        let bullets = &self.bullets;
+        let mut hit_cells = Vec::new();
+        self.swarm.retain(|s| {
+            let hit = bullets.iter().any(|b| b.pos == *s);
+            if hit {
+                hit_cells.push(*s);
+            }
+            !hit
+        });
+        let mut shooters = Vec::new();
+        for b in &mut self.bullets {
+            if hit_cells.contains(&b.pos) && !shooters.iter().any(|(pos, _)| *pos == b.pos) {
+                shooters.push((b.pos, b.owner));
+            }
+            if hit_cells.contains(&b.pos) && !b.hit {
+                b.hit = true;
+                self.wave_hits += 1;
+                self.shots_hit += 1;
+            }
+        }
+        self.kills += hit_cells.len();
+        for (_, owner) in shooters {
+            self.extend_combo(cfg);
+            let points = cfg.swarm_points * self.combo_multiplier();
+            self.award(owner, points);
+            self.log_event(format!("Swarm destroyed +{points}"));
+        }
+
+        // Touching a ship costs it a life; reaching the ships' row removes them
+        let mut rammed = Vec::new();
+        for s in &self.swarm {
+            rammed.extend(self.ships_at(*s));
+        }
+        let row = self.ship_row();
+        self.swarm.retain(|s| s.y < row);
+        for i in rammed {
+            self.hit_ship(cfg, i);
+        }
+    }
+
+    // Launch the mystery UFO from a random side now and then, fly it across the
+    // top row, and pay out a random bonus if a bullet catches it
+    fn tick_ufo(&mut self, cfg: &GameConfig) {
+        if cfg.ufo_every_ticks > 0
+            && self.ufo.is_none()
+            && !self.enemies.is_empty()
+            && self.tick_count.is_multiple_of(cfg.ufo_every_ticks)
+            && self.width > 4
+        {
+            let launch = if self.rng.gen_bool(0.5) {
+                (Pos { x: 1, y: 1 }, 1)
+            } else {
+                (
+                    Pos {
+                        x: self.width - 2,
+                        y: 1,
+                    },
+                    -1,
+                )
+            };
+            self.ufo = Some(launch);
+        }
+        let Some((mut pos, dir)) = self.ufo else {
+            return;
+        };
+        if self.tick_count.is_multiple_of(UFO_MOVE_EVERY_TICKS) {
+            pos.x = (pos.x as i16 + dir as i16) as u16;
+            if pos.x == 0 || pos.x >= self.width - 1 {
+                self.ufo = None;
+                return;
+            }
+            self.ufo = Some((pos, dir));
+        }
+        if let Some(b) = self.bullets.iter_mut().find(|b| b.pos == pos) {
+            if !b.hit {
+                self.wave_hits += 1;
+                self.shots_hit += 1;
+            }
+            b.hit = true;
+            self.ufo = None;
+            self.kills += 1;
+            let owner = b.owner;
+            let bonus = *UFO_BONUSES.choose(&mut self.rng).unwrap();
+            self.award(owner, bonus);
+            self.log_event(format!("UFO destroyed +{bonus}"));
+        }
+    }
+
+    // Maybe leave a random power-up where an enemy was destroyed
+    fn drop_powerup(&mut self, cfg: &GameConfig, pos: Pos) {
+        if cfg.powerup_chance > 0 && self.rng.gen_ratio(cfg.powerup_chance.min(100), 100) {
+            let kind = *PowerUpKind::ALL.choose(&mut self.rng).unwrap();
+            self.powerups.push((pos, kind));
+        }
+    }
+
+    // Run down active buffs, let power-ups fall, and collect those reaching the ship.
+    // Collecting one already active restarts its timer.
+    fn tick_powerups(&mut self, cfg: &GameConfig) {
+        for (_, ticks) in &mut self.buffs {
+            *ticks -= 1;
+        }
+        self.buffs.retain(|(_, ticks)| *ticks > 0);
+        if self.tick_count.is_multiple_of(POWERUP_FALL_EVERY_TICKS) {
+            for (pos, _) in &mut self.powerups {
+                pos.y += 1;
+            }
+        }
+        let ships: Vec<Pos> = self
+            .ships
+            .iter()
+            .filter(|s| s.in_play())
+            .map(|s| s.pos)
+            .collect();
+        let row = self.ship_row();
+        let mut collected = Vec::new();
+        self.powerups.retain(|&(pos, kind)| {
+            let touched = ships.contains(&pos);
+            if touched {
+                collected.push(kind);
+            }
+            !touched && pos.y < row + 1
+        });
+        for kind in collected {
+            self.log_event(format!("Power-up: {}", kind.label()));
+            if kind == PowerUpKind::Speed {
+                self.speed_up(cfg);
+                continue;
+            }
+            self.buffs.retain(|(k, _)| *k != kind);
+            self.buffs.push((kind, cfg.powerup_ticks.max(1)));
+        }
+    }
+
+    // Move the ships a cell further per input, unless already at the cap
+    fn speed_up(&mut self, cfg: &GameConfig) -> bool {
+        let upgraded = self.ship_speed < cfg.player_step + MAX_SPEED_UPGRADES;
+        if upgraded {
+            self.ship_speed += 1;
+        }
+        upgraded
+    }
+
+    // Drop in an asteroid now and then, let asteroids drift down, and resolve what they
+    // run into. Bullets are checked before and after the drift so none slips past one.
+    fn tick_obstacles(&mut self, cfg: &GameConfig) {
+        if cfg.asteroid_every_ticks > 0
+            && !self.enemies.is_empty()
+            && self.tick_count.is_multiple_of(cfg.asteroid_every_ticks)
+            && self.width > 4
+        {
+            let x = self.rng.gen_range(1..self.width - 1);
+            self.obstacles.push(Pos { x, y: 1 });
+        }
+        self.block_bullets();
+        if self.tick_count.is_multiple_of(ASTEROID_FALL_EVERY_TICKS) {
+            for pos in &mut self.obstacles {
+                pos.y += 1;
+            }
+            self.block_bullets();
+        }
+
+        // Touching a ship costs it a life and breaks the asteroid up; the rest leave at the ships' row
+        let mut rammed = Vec::new();
+        let mut wrecked = Vec::new();
+        for &pos in &self.obstacles {
+            let hit = self.ships_at(pos);
+            if !hit.is_empty() {
+                wrecked.push(pos);
+                rammed.extend(hit);
+            }
+        }
+        let row = self.ship_row();
+        self.obstacles
+            .retain(|pos| pos.y < row && !wrecked.contains(pos));
+        for i in rammed {
+            if self.ships[i].invulnerable == 0 {
+                self.log_event("Asteroid impact");
+            }
+            self.hit_ship(cfg, i);
+        }
+    }
+
+    // Asteroids soak up every shot, from the player and the invaders alike
+    fn block_bullets(&mut self) {
+        let obstacles = &self.obstacles;
+        self.bullets.retain(|b| !obstacles.contains(&b.pos));
+        self.enemy_bullets.retain(|b| !obstacles.contains(&b.pos));
+    }
+
+    fn buff_active(&self, kind: PowerUpKind) -> bool {
+        self.buffs.iter().any(|(k, _)| *k == kind)
+    }
+
+    // Heat a shot adds; rapid fire halves it
+    fn shot_heat(&self, cfg: &GameConfig) -> u32 {
+        if self.buff_active(PowerUpKind::Rapid) {
+            cfg.heat_per_shot / 2
+        } else {
+            cfg.heat_per_shot
+        }
+    }
+
+    // Build up heat in ship `i`'s gun, locking it once the gauge tops out
+    fn add_heat(&mut self, cfg: &GameConfig, i: usize) {
+        let heat = self.shot_heat(cfg);
+        let ship = &mut self.ships[i];
+        ship.heat += heat;
+        if ship.heat >= MAX_HEAT {
+            ship.heat = MAX_HEAT;
+            ship.overheated = true;
+        }
+    }
+
+    // The lowest enemy of each column, ordered left to right
+    fn column_leaders(&self) -> Vec<Pos> {
+        let mut leaders: BTreeMap<u16, Pos> = BTreeMap::new();
+        for e in &self.enemies {
+            let leader = leaders.entry(e.pos.x).or_insert(e.pos);
+            if e.pos.y > leader.y {
+                *leader = e.pos;
+            }
+        }
+        leaders.into_values().collect()
+    }
+
+    // Pick the enemy that fires next according to the strategy
+    fn choose_shooter(&mut self, strategy: FireStrategy) -> Option<Pos> {
+        let leaders = self.column_leaders();
+        if leaders.is_empty() {
+            return None;
+        }
+        match strategy {
+            FireStrategy::Random => leaders.choose(&mut self.rng).copied(),
+            FireStrategy::RoundRobin => {
+                let shooter = leaders[self.fire_cursor % leaders.len()];
+                self.fire_cursor = self.fire_cursor.wrapping_add(1);
+                Some(shooter)
+            }
+            FireStrategy::Leftmost => leaders.first().copied(),
+            FireStrategy::Targeted => {
+                let weights = leaders
+                    .iter()
+                    .map(|l| match l.x.abs_diff(self.target(l.x).x) {
+                        0 => 8,
+                        1..=2 => 4,
+                        _ => 1,
+                    });
+                let dist = WeightedIndex::new(weights).ok()?;
+                Some(leaders[dist.sample(&mut self.rng)])
+            }
+        }
+    }
+
+    // Fire one enemy bullet from the chosen column leader, unless the cap is reached
+    fn enemy_fire(&mut self, cfg: &GameConfig) {
+        if self.enemy_bullets.len() >= cfg.max_enemy_bullets {
+            return;
+        }
+        // A boss always aims at the player
+        if let Some(boss) = self.boss {
+            let start = boss.muzzle();
+            for dx in self.volley(BulletPattern::Aimed, start) {
+                self.enemy_bullets.push(EnemyBullet::new(start, dx));
+            }
+            return;
+        }
+        let Some(shooter) = self.choose_shooter(cfg.fire_strategy) else {
+            return;
+        };
+        let start = Pos {
+            x: shooter.x,
+            y: shooter.y + 1,
+        };
+        // A shot fired straight into the enemy below is stopped at once
+        if cfg.formation_blocks_fire && self.enemies.iter().any(|e| e.pos == start) {
+            return;
+        }
+        let patterns = &cfg.enemy_bullet_patterns;
+        let pattern = patterns
+            .get((self.level - 1) % patterns.len().max(1))
+            .copied()
+            .unwrap_or(BulletPattern::Straight);
+        for dx in self.volley(pattern, start) {
+            if self.enemy_bullets.len() >= cfg.max_enemy_bullets {
+                break;
+            }
+            self.enemy_bullets.push(EnemyBullet::new(start, dx));
+        }
+    }
+
+    // Per-row drift of each shot in a volley fired from `start`
+    fn volley(&self, pattern: BulletPattern, start: Pos) -> Vec<f32> {
+        match pattern {
+            BulletPattern::Straight => vec![0.0],
+            BulletPattern::Spread => vec![0.0, -0.5, 0.5],
+            BulletPattern::Aimed => {
+                let target = self.target(start.x);
+                let rows = target.y.saturating_sub(start.y).max(1) as f32;
+                let dx = (target.x as f32 - start.x as f32) / rows;
+                vec![dx.clamp(-1.0, 1.0)]
+            }
+        }
+    }
+
+    // Move enemy bullets down (angled ones also sideways), dropping those that leave the play
+    // area and, when the formation blocks fire, those running into another enemy
+    fn move_enemy_bullets(&mut self, cfg: &GameConfig) {
+        for b in &mut self.enemy_bullets {
+            b.pos.y += 1;
+            b.x += b.dx;
+            b.pos.x = b.x.round().max(0.0) as u16;
+        }
+        let floor = self.height.saturating_sub(2);
+        let right_wall = self.width.saturating_sub(1);
+        let enemies = &self.enemies;
+        let blocked =
+            |b: &EnemyBullet| cfg.formation_blocks_fire && enemies.iter().any(|e| e.pos == b.pos);
+        self.enemy_bullets
+            .retain(|b| b.pos.y < floor && b.pos.x > 0 && b.pos.x < right_wall && !blocked(b));
+    }
+
+    // Move bullets up (angled ones also sideways) and drop those hitting a wall
+    fn move_bullets(&mut self, cfg: &GameConfig) {
+        for b in self.bullets.iter_mut() {
+            if b.pos.y > 0 {
+                b.pos.y -= 1;
+            }
+            b.pos.x = (b.pos.x as i16 + b.dx as i16).max(0) as u16;
+        }
+        let right_wall = self.width.saturating_sub(1);
+        let on_board = |b: &Bullet| b.pos.y > 0 && b.pos.x > 0 && b.pos.x < right_wall;
+        let missed = self.bullets.iter().any(|b| !b.hit && !on_board(b));
+        self.bullets.retain(on_board);
+        if missed && cfg.combo_breaks_on_miss {
+            self.break_combo();
+        }
+    }
+
+    // Begin an aim-trainer session: the usual board and bullets, one target at a time
+    fn start_trainer(&mut self, cfg: &GameConfig) {
+        self.reset(cfg);
+        self.enemies.clear();
+        self.enemy_targets.clear();
+        self.spawn_anim = 0;
+        self.awaiting_ready = false;
+        self.bullets.clear();
+        self.shields.clear();
+        self.events.clear();
+        self.revert_wave_modifier();
+        let target = self.trainer_spot();
+        self.trainer = Some(Trainer {
+            target,
+            direction: 1,
+            spawned_tick: 0,
+            remaining: cfg.trainer_targets.max(1),
+            reaction_ticks: Vec::new(),
+        });
+    }
+
+    // A random spot in the upper half of the board for the next target
+    fn trainer_spot(&mut self) -> Pos {
+        let x = self.rng.gen_range(2..=self.width.saturating_sub(3).max(2));
+        let y = self.rng.gen_range(2..=(self.height / 2).max(2));
+        Pos { x, y }
+    }
+
+    // Trainer tick: no descent, no enemy fire and no way to lose
+    fn tick_trainer(&mut self, cfg: &GameConfig) {
+        self.move_bullets(cfg);
+        let (lo, hi) = (2, self.width.saturating_sub(3).max(2));
+        let tick = self.tick_count;
+        let Some(trainer) = &mut self.trainer else {
+            return;
+        };
+
+        // Moving targets sweep back and forth between the walls
+        let every = cfg.trainer_target_move_every_ticks;
+        if every > 0 && tick.is_multiple_of(every) {
+            let t = &mut trainer.target;
+            if (trainer.direction > 0 && t.x >= hi) || (trainer.direction < 0 && t.x <= lo) {
+                trainer.direction *= -1;
+            }
+            t.x = (t.x as i16 + trainer.direction as i16).clamp(lo as i16, hi as i16) as u16;
+        }
+
+        let target = trainer.target;
+        let Some(bi) = self.bullets.iter().position(|b| b.pos == target) else {
+            return;
+        };
+        self.bullets.remove(bi);
+        self.kills += 1;
+        self.shots_hit += 1;
+        trainer.reaction_ticks.push(tick - trainer.spawned_tick);
+        trainer.remaining -= 1;
+        if trainer.remaining == 0 {
+            self.victory = true;
+            return;
+        }
+        let next = self.trainer_spot();
+        if let Some(trainer) = &mut self.trainer {
+            trainer.target = next;
+            trainer.spawned_tick = tick;
+        }
+    }
+
+    // Player and enemy bullets that share a cell, or swapped cells this tick
+    // (passing through each other), destroy each other with a spark
+    fn cancel_bullets(&mut self) {
+        // Where each bullet was before this tick's move
+        let player_prev = |b: &Bullet| Pos {
+            x: (b.pos.x as i16 - b.dx as i16).max(0) as u16,
+            y: b.pos.y + 1,
+        };
+        let enemy_prev = |b: &EnemyBullet| Pos {
+            x: (b.x - b.dx).round().max(0.0) as u16,
+            y: b.pos.y.saturating_sub(1),
+        };
+        let mut spent = Vec::new();
+        for (pi, p) in self.bullets.iter().enumerate() {
+            let crossed = |e: &EnemyBullet| {
+                e.pos == p.pos || (enemy_prev(e) == p.pos && e.pos == player_prev(p))
+            };
+            if let Some(ei) = self.enemy_bullets.iter().position(crossed) {
+                spent.push((pi, self.enemy_bullets.remove(ei)));
+            }
+        }
+        for (pi, e) in spent.iter().rev() {
+            self.bullets.remove(*pi);
+            self.sparks.insert(0, (e.pos, SPARK_TTL_TICKS));
+        }
+        self.sparks.truncate(SPARK_CAP);
+    }
+
+    // The first player's shot press: fires normally, or starts or sustains a beam charge.
+    // Pressing again quickly (or holding the key) while the beam is ready starts charging.
+    fn press_shoot(&mut self, cfg: &GameConfig) {
+        if !self.ships[0].in_play() {
+            return;
+        }
+        let last = self.last_shot_tick.replace(self.tick_count);
+        if self.beam_charge.is_some() {
+            return;
+        }
+        let quick = last.is_some_and(|t| self.tick_count - t <= BEAM_DOUBLE_PRESS_TICKS);
+        if quick && cfg.beam_charge_ticks > 0 && self.beam_cooldown == 0 && self.trainer.is_none() {
+            self.beam_charge = Some(0);
+            return;
+        }
+        self.shoot(cfg, 0);
+    }
+
+    // Build up a beam charge while presses keep coming; once they stop, a full charge
+    // fires and anything less fizzles
+    fn tick_beam(&mut self, cfg: &GameConfig) {
+        let Some(charge) = self.beam_charge else {
+            return;
+        };
+        let held = self
+            .last_shot_tick
+            .is_some_and(|t| self.tick_count - t <= BEAM_RELEASE_TICKS);
+        if held {
+            self.beam_charge = Some(charge + 1);
+            return;
+        }
+        self.beam_charge = None;
+        if charge >= cfg.beam_charge_ticks {
+            self.fire_beam(cfg);
+        }
+    }
+
+    // Whether a charging beam has enough charge to fire on release
+    fn beam_charged(&self, cfg: &GameConfig) -> bool {
+        self.beam_charge.is_some_and(|c| c >= cfg.beam_charge_ticks)
+    }
+
+    // Destroy every enemy in the player's column at once and burn into a boss above
+    fn fire_beam(&mut self, cfg: &GameConfig) {
+        if !self.ships[0].in_play() {
+            return;
+        }
+        let x = self.ships[0].pos.x;
+        self.beam = Some((x, BEAM_FLASH_TICKS));
+        self.beam_cooldown = cfg.beam_cooldown_ticks;
+        self.shots_fired += 1;
+        self.wave_shots += 1;
+        let mut total = 0;
+        let mut hit = Vec::new();
+        for (i, e) in self.enemies.iter().enumerate() {
+            if e.pos.x == x && e.pos.y < self.ship_row() {
+                hit.push(i);
+            }
+        }
+        for &i in &hit {
+            self.extend_combo(cfg);
+            let points =
+                self.kill_points(cfg) * self.enemies[i].kind.points() * self.combo_multiplier();
+            total += points;
+            self.kills += 1;
+            self.wave_kills += 1;
+            let pos = self.enemies[i].pos;
+            self.drop_powerup(cfg, pos);
+        }
+        for &i in hit.iter().rev() {
+            self.enemies.remove(i);
+        }
+        if !hit.is_empty() {
+            self.wave_hits += 1;
+            self.shots_hit += 1;
+            self.award(0, total);
+            self.enemy_panic = cfg.enemy_panic_ticks;
+            self.log_event(format!("Beam x{} +{total}", hit.len()));
+        }
+        let Some(mut boss) = self.boss else {
+            return;
+        };
+        if x < boss.pos.x || x >= boss.pos.x + BOSS_WIDTH {
+            return;
+        }
+        if hit.is_empty() {
+            self.wave_hits += 1;
+            self.shots_hit += 1;
+        }
+        boss.hp = boss.hp.saturating_sub(BEAM_BOSS_DAMAGE);
+        self.award(0, cfg.kill_points * BEAM_BOSS_DAMAGE as usize);
+        if boss.hp > 0 {
+            self.boss = Some(boss);
+            return;
+        }
+        self.boss = None;
+        self.kills += 1;
+        self.wave_kills += 1;
+        let points = cfg.kill_points * BOSS_KILL_MULTIPLIER;
+        self.award(0, points);
+        self.log_event(format!("Boss destroyed +{points}"));
+    }
+
+    // Column offsets of a shot's bullets from the ship; the pattern widens with the weapon level
+    fn shot_pattern(&self) -> &'static [i16] {
+        match self.weapon_level {
+            _ if self.buff_active(PowerUpKind::Spread) => &[-1, 0, 1],
+            1 => &[0],
+            2 => &[-1, 1],
+            _ => &[-1, 0, 1],
+        }
+    }
+
+    // Aim assist: for each bullet of the first ship's next shot, its column and the
+    // row its path reaches up to. A path ends below the first thing that would stop
+    // the bullet now: an enemy, a swarm enemy, the boss, or a shield that absorbs
+    // player shots. A clear path runs to the top of the board.
+    fn assist_paths(&self, cfg: &GameConfig) -> Vec<(u16, u16)> {
+        let ship = self.ships[0];
+        if !ship.in_play() {
+            return Vec::new();
+        }
+        let shields = self
+            .shields
+            .iter()
+            .filter(|_| cfg.shields_absorb_player_shots)
+            .map(|s| s.pos);
+        let boss = self.boss.iter().flat_map(|b| b.cells().map(|(pos, _)| pos));
+        let blockers: Vec<Pos> = self
+            .enemies
+            .iter()
+            .map(|e| e.pos)
+            .chain(self.swarm.iter().copied())
+            .chain(boss)
+            .chain(shields)
+            .collect();
+        self.shot_pattern()
+            .iter()
+            .map(|dx| ship.pos.x as i16 + dx)
+            .filter(|&x| x >= 1 && x <= self.width as i16 - 2)
+            .map(|x| {
+                let x = x as u16;
+                let stop = blockers
+                    .iter()
+                    .filter(|b| b.x == x && b.y < ship.pos.y)
+                    .map(|b| b.y + 1)
+                    .max();
+                (x, stop.unwrap_or(1))
+            })
+            .collect()
+    }
+
+    // Ship `i` shooting
+    fn shoot(&mut self, cfg: &GameConfig, i: usize) {
+        let ship = self.ships[i];
+        if !ship.in_play() {
+            return;
+        }
+        let pattern = self.shot_pattern();
+        if ship.overheated || self.magazine_empty(cfg) {
+            self.blocked_shot = BLOCKED_SHOT_TICKS;
+            return;
+        }
+        // The whole pattern is one trigger pull's worth of heat
+        self.add_heat(cfg, i);
+        for dx in pattern {
+            if self.magazine_empty(cfg) {
+                break;
+            }
+            let x = ship.pos.x as i16 + dx;
+            if x < 1 || x > self.width as i16 - 2 {
+                continue;
+            }
+            self.bullets.push(Bullet {
+                pos: Pos {
+                    x: x as u16,
+                    y: ship.pos.y.saturating_sub(1),
+                },
+                tier: self.weapon_level,
+                dx: 0,
+                hit: false,
+                pierce: cfg.pierce_hits,
+                owner: i,
+            });
+            self.shots_fired += 1;
+            self.wave_shots += 1;
+            self.spend_round(cfg);
+        }
+    }
+
+    // Fire a single angled shot drifting `dx` cells sideways per row
+    fn shoot_diagonal(&mut self, cfg: &GameConfig, dx: i8) {
+        if !cfg.diagonal_shots {
+            return;
+        }
+        let ship = self.ships[0];
+        if !ship.in_play() {
+            return;
+        }
+        if ship.overheated || self.magazine_empty(cfg) {
+            self.blocked_shot = BLOCKED_SHOT_TICKS;
+            return;
+        }
+        let x = ship.pos.x as i16 + dx as i16;
+        if x < 1 || x > self.width as i16 - 2 {
+            return;
+        }
+        self.add_heat(cfg, 0);
+        self.bullets.push(Bullet {
+            pos: Pos {
+                x: x as u16,
+                y: ship.pos.y.saturating_sub(1),
+            },
+            tier: self.weapon_level,
+            dx,
+            hit: false,
+            pierce: cfg.pierce_hits,
+            owner: 0,
+        });
+        self.shots_fired += 1;
+        self.wave_shots += 1;
+        self.spend_round(cfg);
+    }
+
+    // With a magazine, nothing fires while it is empty or reloading
+    fn magazine_empty(&self, cfg: &GameConfig) -> bool {
+        cfg.magazine_size > 0 && (self.ammo_in_mag == 0 || self.reload_ticks > 0)
+    }
+
+    // Take a round from the magazine, reloading automatically once it runs dry
+    fn spend_round(&mut self, cfg: &GameConfig) {
+        if cfg.magazine_size == 0 {
+            return;
+        }
+        self.ammo_in_mag = self.ammo_in_mag.saturating_sub(1);
+        if self.ammo_in_mag == 0 {
+            self.start_reload(cfg);
+        }
+    }
+
+    // Begin refilling the magazine, unless it is full or already reloading
+    fn start_reload(&mut self, cfg: &GameConfig) {
+        if cfg.magazine_size > 0 && self.ammo_in_mag < cfg.magazine_size && self.reload_ticks == 0 {
+            self.reload_ticks = cfg.reload_ticks.max(1);
+        }
+    }
+
+    // Apply a player input, recording it while the run is live
+    fn apply_input(&mut self, input: ReplayInput, cfg: &GameConfig) {
+        if !self.game_over && !self.victory {
+            self.inputs.push((self.tick_count, input));
+        }
+        match input {
+            ReplayInput::Left => self.move_ship_left(0),
+            ReplayInput::Right => self.move_ship_right(0),
+            ReplayInput::Shoot => self.press_shoot(cfg),
+            ReplayInput::ShootLeft => self.shoot_diagonal(cfg, -1),
+            ReplayInput::ShootRight => self.shoot_diagonal(cfg, 1),
+            ReplayInput::Turbo => self.turbo_ticks = cfg.turbo_hold_ticks,
+            ReplayInput::Resize(w, h) => self.resize(w, h),
+            ReplayInput::SkipSpawn => {
+                self.finish_spawn_anim();
+                // The next tick ends a wave-complete interlude
+                self.wave_clear_anim = self.wave_clear_anim.min(1);
+            }
+            ReplayInput::Reload => self.start_reload(cfg),
+            ReplayInput::Bomb => self.smart_bomb(cfg),
+            // Second-player inputs command the invaders in versus, the second ship in co-op
+            ReplayInput::P2Left if self.versus => self.steer_invaders(-1),
+            ReplayInput::P2Right if self.versus => self.steer_invaders(1),
+            ReplayInput::P2Shoot if self.versus => self.invaders_fire(cfg),
+            ReplayInput::P2Left if self.ships.len() > 1 => self.move_ship_left(1),
+            ReplayInput::P2Right if self.ships.len() > 1 => self.move_ship_right(1),
+            ReplayInput::P2Shoot if self.ships.len() > 1 => self.shoot(cfg, 1),
+            ReplayInput::P2Left | ReplayInput::P2Right | ReplayInput::P2Shoot => {}
+        }
+    }
+
+    // Smart bomb: destroy every enemy and swarm enemy on screen within `bomb_reach` rows
+    // above the ships, for half their usual points and without feeding the combo.
+    // Bosses shrug it off, and it can't be set off while a wave drops in.
+    fn smart_bomb(&mut self, cfg: &GameConfig) {
+        if self.bombs == 0 || self.spawn_anim > 0 || self.awaiting_ready || self.trainer.is_some() {
+            return;
+        }
+        self.bombs -= 1;
+        let floor = self.ship_row();
+        let top = floor.saturating_sub(cfg.bomb_reach).max(1);
+        let right_wall = self.width.saturating_sub(1);
+        let in_blast = |p: Pos| p.y >= top && p.y <= floor && p.x > 0 && p.x < right_wall;
+        let base = self.kill_points(cfg);
+        let mut points = 0;
+        let mut blasted = Vec::new();
+        self.enemies.retain(|e| {
+            let hit = in_blast(e.pos);
+            if hit {
+                points += base * e.kind.points() / 2;
+                blasted.push(e.pos);
+            }
+            !hit
+        });
+        let formation_kills = blasted.len();
+        self.swarm.retain(|&s| {
+            let hit = in_blast(s);
+            if hit {
+                points += cfg.swarm_points / 2;
+                blasted.push(s);
+            }
+            !hit
+        });
+        self.kills += blasted.len();
+        self.wave_kills += formation_kills;
+        self.award(0, points);
+        for pos in &blasted {
+            self.sparks.insert(0, (*pos, SPARK_TTL_TICKS));
+        }
+        self.sparks.truncate(SPARK_CAP);
+        self.log_event(format!("Smart bomb: {} down +{points}", blasted.len()));
+    }
+
+    // Versus: point the formation's march. It still drops and turns back at a wall,
+    // and can't be steered straight back into the wall it just left.
+    fn steer_invaders(&mut self, direction: i8) {
+        let slots = self.enemies.iter().map(|e| e.slot().x);
+        let blocked = if direction > 0 {
+            slots
+                .max()
+                .is_some_and(|x| x >= self.width.saturating_sub(3))
+        } else {
+            slots.min().is_some_and(|x| x <= 2)
+        };
+        if !blocked {
+            self.enemy_direction = direction;
+        }
+    }
+
+    // Versus: fire a volley now if the invaders have reloaded, from the column the
+    // fire strategy picks. Nothing fires while a wave drops in.
+    fn invaders_fire(&mut self, cfg: &GameConfig) {
+        let reload = self.fire_interval(cfg);
+        if self.versus_reload > 0
+            || reload == 0
+            || self.spawn_anim > 0
+            || self.awaiting_ready
+            || self.wave_clear_anim > 0
+        {
+            return;
+        }
+        self.enemy_fire(cfg);
+        self.versus_reload = reload;
+    }
+
+    // Ship movement, `ship_speed` cells at a time, clamped to the walls or wrapping
+    // around them. Co-op ships pass through each other.
+    fn move_ship_left(&mut self, i: usize) {
+        let span = self.width.saturating_sub(2).max(1);
+        let ship = &mut self.ships[i];
+        ship.pos.x = if self.wrap_ship {
+            (ship.pos.x - 1 + span - self.ship_speed % span) % span + 1
+        } else {
+            ship.pos.x.saturating_sub(self.ship_speed).max(1)
+        };
+    }
+    fn move_ship_right(&mut self, i: usize) {
+        let span = self.width.saturating_sub(2).max(1);
+        let ship = &mut self.ships[i];
+        ship.pos.x = if self.wrap_ship {
+            (ship.pos.x - 1 + self.ship_speed % span) % span + 1
+        } else {
+            (ship.pos.x + self.ship_speed).min(self.width.saturating_sub(2))
+        };
+    }
+
+    // Adapt to a new board size, keeping every entity at the same relative spot.
+    // Enemies that would land on the same cell are nudged to the nearest free one.
+    fn resize(&mut self, width: u16, height: u16) {
+        let (old_w, old_h) = (self.width, self.height);
+        if (width, height) == (old_w, old_h) {
+            return;
+        }
+        self.finish_spawn_anim();
+        self.width = width;
+        self.height = height;
+        // Map a coordinate in lo..=old_hi to lo..=new_hi, rounding to the nearest cell
+        fn scale(v: u16, lo: u16, old_hi: u16, new_hi: u16) -> u16 {
+            if old_hi <= lo || new_hi <= lo {
+                return lo.max(v.min(new_hi));
+            }
+            let rel = (v.clamp(lo, old_hi) - lo) as f64 / (old_hi - lo) as f64;
+            lo + (rel * (new_hi - lo) as f64).round() as u16
+        }
+        let rescale = |p: Pos, x_lo: u16, x_margin: u16, y_hi: u16| Pos {
+            x: scale(
+                p.x,
+                x_lo,
+                old_w.saturating_sub(x_margin),
+                width.saturating_sub(x_margin),
+            ),
+            y: scale(
+                p.y,
+                1,
+                old_h.saturating_sub(y_hi),
+                height.saturating_sub(y_hi),
+            ),
+        };
+
+        for ship in &mut self.ships {
+            ship.pos = Pos {
+                x: rescale(ship.pos, 1, 2, 3).x,
+                y: height.saturating_sub(3),
+            };
+        }
+        for b in &mut self.bullets {
+            b.pos = rescale(b.pos, 1, 2, 2);
+        }
+        for b in &mut self.enemy_bullets {
+            *b = EnemyBullet::new(rescale(b.pos, 1, 2, 2), b.dx);
+        }
+        for s in &mut self.swarm {
+            *s = rescale(*s, 1, 2, 3);
+        }
+        if let Some((pos, _)) = &mut self.ufo {
+            pos.x = rescale(*pos, 1, 2, 3).x;
+        }
+        if let Some(boss) = &mut self.boss {
+            let max_x = width.saturating_sub(BOSS_WIDTH + 2).max(2);
+            boss.pos.x = rescale(boss.pos, 2, BOSS_WIDTH + 2, 3).x.clamp(2, max_x);
+        }
+        for (pos, _) in &mut self.powerups {
+            *pos = rescale(*pos, 1, 2, 3);
+        }
+        for pos in &mut self.obstacles {
+            *pos = rescale(*pos, 1, 2, 3);
+        }
+        // Bunkers keep their height above the player's row; cells squeezed together merge
+        let mut shields: Vec<ShieldCell> = Vec::with_capacity(self.shields.len());
+        for cell in &self.shields {
+            let pos = Pos {
+                x: rescale(cell.pos, 1, 2, 3).x,
+                y: (cell.pos.y + height).saturating_sub(old_h),
+            };
+            if pos.y > height / 2 && !shields.iter().any(|s| s.pos == pos) {
+                shields.push(ShieldCell { pos, hp: cell.hp });
+            }
+        }
+        self.shields = shields;
+        let (x_lo, x_hi) = (2, width.saturating_sub(3).max(2));
+        let mut placed: Vec<Enemy> = Vec::with_capacity(self.enemies.len());
+        // Divers are called back to their slots first
+        for e in &self.enemies {
+            let target = rescale(e.slot(), 2, 3, 3);
+            let free = (0..=x_hi - x_lo)
+                .flat_map(|d| [target.x.checked_sub(d), Some(target.x + d)])
+                .flatten()
+                .filter(|x| (x_lo..=x_hi).contains(x))
+                .map(|x| Pos { x, y: target.y })
+                .find(|p| !placed.iter().any(|e| e.pos == *p));
+            // A row that can no longer hold its enemies keeps the overflow stacked
+            placed.push(Enemy::new(free.unwrap_or(target), e.kind));
+        }
+        self.enemies = placed;
+    }
+
+    fn enemies_remaining(&self) -> usize {
+        match &self.trainer {
+            Some(trainer) => trainer.remaining,
+            None => self.enemies.len(),
+        }
+    }
+
+    // Progress indicator (for the info panel)
+    fn progress(&self) -> f64 {
+        let total_expected = (self.spawn_rows * self.spawn_cols).max(1) + (self.level - 1) * 2;
+        (self.kills as f64 / total_expected as f64).min(1.0)
+    }
+}
+
+// Tick down a newest-first list of timed entries: expired ones are removed and
+// anything past `cap` (the oldest) is dropped
+fn prune_ttl<T>(list: &mut Vec<(T, u8)>, cap: usize) {
+    for (_, ttl) in list.iter_mut() {
+        *ttl = ttl.saturating_sub(1);
+    }
+    list.retain(|(_, ttl)| *ttl > 0);
+    list.truncate(cap);
+}
+
+// Player bullet color: a running combo's multiplier outranks the weapon tier's color
+fn bullet_color(gs: &GameState, cfg: &GameConfig, tier_color: Color) -> Color {
+    if !cfg.state_bullet_colors {
+        return Color::Yellow;
+    }
+    match gs.combo_multiplier() {
+        1 => tier_color,
+        2 => Color::LightCyan,
+        3 => Color::LightGreen,
+        _ => Color::White,
+    }
+}
+
+// What a board cell shows when several things land on it: the highest layer wins,
+// and within a layer the last one drawn
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Layer {
+    Empty,
+    // Aiming guides: the reticle and the aim-assist path
+    Guide,
+    Shield,
+    Obstacle,
+    // Enemies, swarm enemies and trainer targets
+    Enemy,
+    // Edge arrows and the aim-assist highlight, which sit on top of enemies
+    Marker,
+    PowerUp,
+    Player,
+    EnemyBullet,
+    Bullet,
+    // Sparks and fireworks
+    Effect,
+    Ground,
+}
+
+// Place a glyph at a board position (board coordinates include the border cells),
+// unless something on a higher layer already occupies the cell
+fn plot(grid: &mut [Vec<(char, Style, Layer)>], p: Pos, layer: Layer, cell: (char, Style)) {
+    if p.x == 0 || p.y == 0 {
+        return;
+    }
+    if let Some(slot) = grid
+        .get_mut(p.y as usize - 1)
+        .and_then(|row| row.get_mut(p.x as usize - 1))
+        .filter(|slot| slot.2 <= layer)
+    {
+        *slot = (cell.0, cell.1, layer);
+    }
+}
+
+// Draw the main play area
+fn draw_game<B: ratatui::backend::Backend>(
+    f: &mut ratatui::Frame<B>,
+    area: Rect,
+    gs: &GameState,
+    cfg: &GameConfig,
+) {
+    let mut block = Block::default().borders(Borders::ALL).title(Span::styled(
+        format!(" Space Invaders - Level {} ", gs.level),
+        Style::default()
+            .fg(Color::LightGreen)
+            .add_modifier(Modifier::BOLD),
+    ));
+    // The border flashes during a hit-stop
+    if gs.hitstop > 0 {
+        block = block.border_style(
+            Style::default()
+                .fg(Color::White)
+                .add_modifier(Modifier::BOLD),
+        );
+    } else if let Some(color) = cfg
+        .wave_intro_flash
+        .filter(|_| gs.awaiting_ready || gs.spawn_anim % 2 == 1)
+    {
+        // ...and in the intro color while a wave drops in (blinking) or waits (steady)
+        block = block.border_style(Style::default().fg(color).add_modifier(Modifier::BOLD));
+    }
+    f.render_widget(block, area);
+
+    let inner = Rect {
+        x: area.x + 1,
+        y: area.y + 1,
+        width: area.width.saturating_sub(2),
+        height: area.height.saturating_sub(2),
+    };
+
+    // Prepare 2D char grid for rendering entities
+    let mut grid = vec![
+        vec![(' ', Style::default(), Layer::Empty); inner.width as usize];
+        inner.height as usize
+    ];
+
+    // Draw the aiming reticle; everything else renders on top of it
+    let p = gs.ships[0].pos;
+    if cfg.show_reticle {
+        for y in 1..p.y {
+            plot(
+                &mut grid,
+                Pos { x: p.x, y },
+                Layer::Guide,
+                ('·', Style::default().fg(Color::DarkGray)),
+            );
+        }
+    }
+
+    // Aim assist: the paths of the next shot's bullets, each up to what it would hit now
+    let assist_paths = if cfg.aim_assist {
+        gs.assist_paths(cfg)
+    } else {
+        Vec::new()
+    };
+    for &(x, top) in &assist_paths {
+        for y in top..p.y {
+            plot(
+                &mut grid,
+                Pos { x, y },
+                Layer::Guide,
+                ('┆', Style::default().fg(Color::Gray)),
+            );
+        }
+    }
+
+    // Draw the shield bunkers, looking more battered the fewer hits they have left
+    for cell in &gs.shields {
+        let glyph = match cell.hp {
+            SHIELD_HP.. => '█',
+            2 => '▓',
+            _ => '░',
+        };
+        plot(
+            &mut grid,
+            cell.pos,
+            Layer::Shield,
+            (glyph, Style::default().fg(Color::Green)),
+        );
+    }
+
+    // Draw the trainer's target
+    if let Some(trainer) = &gs.trainer {
+        plot(
+            &mut grid,
+            trainer.target,
+            Layer::Enemy,
+            (
+                '◎',
+                Style::default()
+                    .fg(Color::LightRed)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        );
+    }
+
+    // Draw enemies, angrier the closer the lowest one is to the player row:
+    // no anger down to half the board, full anger right above the player
+    let anger = gs
+        .enemies
+        .iter()
+        .map(|e| e.pos.y)
+        .max()
+        .filter(|_| cfg.enemy_anger)
+        .map_or(0.0, |lowest| {
+            let span = (p.y / 2).max(1) as f32;
+            (1.0 - p.y.saturating_sub(lowest) as f32 / span).clamp(0.0, 1.0)
+        });
+    // Each kind has its own color unless the wave was given a random one
+    let mut enemy_style = Style::default().add_modifier(Modifier::BOLD);
+    let mut wave_color = cfg.randomize_enemy_colors.then_some(gs.enemy_color);
+    if anger > 0.0 {
+        wave_color = Some(if anger < 0.5 {
+            Color::LightRed
+        } else {
+            Color::Red
+        });
+    }
+    // Past the halfway mark they flash, faster as they close in
+    if anger >= 0.5 {
+        let period = 6 - (anger * 4.0) as u64;
+        if (gs.tick_count / period) % 2 == 1 {
+            enemy_style = enemy_style.add_modifier(Modifier::REVERSED);
+        }
+    }
+    // Fog hides the formation's color and its anger alike
+    if gs.wave_modifier == Some(WaveModifier::Fog) {
+        enemy_style = Style::default();
+        wave_color = Some(Color::DarkGray);
+    }
+    // Right after a descent the formation trails behind, squashed flat, until it settles
+    let lag = gs.descent_lag(cfg);
+    for e in &gs.enemies {
+        let drawn = Pos {
+            x: e.pos.x,
+            y: e.pos.y.saturating_sub(lag),
+        };
+        let glyph = if lag > 0 { '▄' } else { e.kind.glyph() };
+        let style = enemy_style.fg(wave_color.unwrap_or(e.kind.color()));
+        plot(&mut grid, drawn, Layer::Enemy, (glyph, style));
+    }
+
+    // Draw the boss, flashing on the ticks it is hit hard enough to be in trouble
+    if let Some(boss) = &gs.boss {
+        let low = boss.hp * 4 <= boss.max_hp;
+        let mut style = Style::default()
+            .fg(Color::LightMagenta)
+            .add_modifier(Modifier::BOLD);
+        if low && gs.tick_count % 4 < 2 {
+            style = style.add_modifier(Modifier::REVERSED);
+        }
+        for (pos, glyph) in boss.cells() {
+            plot(&mut grid, pos, Layer::Enemy, (glyph, style));
+        }
+    }
+
+    // Draw the mystery UFO
+    if let Some((pos, _)) = gs.ufo {
+        plot(
+            &mut grid,
+            pos,
+            Layer::Enemy,
+            (
+                '@',
+                Style::default()
+                    .fg(Color::LightRed)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        );
+    }
+
+    // Draw falling power-ups
+    for &(pos, kind) in &gs.powerups {
+        plot(
+            &mut grid,
+            pos,
+            Layer::PowerUp,
+            (
+                kind.glyph(),
+                Style::default()
+                    .fg(kind.color())
+                    .add_modifier(Modifier::BOLD),
+            ),
+        );
+    }
+
+    // Draw asteroids
+    for &pos in &gs.obstacles {
+        plot(
+            &mut grid,
+            pos,
+            Layer::Obstacle,
+            ('◆', Style::default().fg(Color::Gray)),
+        );
+    }
+
+    // Draw swarm enemies with a flickering glyph
+    let swarm_glyph = if gs.tick_count.is_multiple_of(2) {
+        'x'
+    } else {
+        '+'
+    };
+    for s in &gs.swarm {
+        plot(
+            &mut grid,
+            *s,
+            Layer::Enemy,
+            (
+                swarm_glyph,
+                Style::default()
+                    .fg(Color::LightMagenta)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        );
+    }
+
+    // Mark hostiles outside the visible playfield (a wave dropping in from above,
+    // or anything a shrunken terminal clips) with an arrow on the nearest edge
+    if cfg.edge_markers {
+        let (w, h) = (inner.width, inner.height);
+        for e in gs.enemies.iter().map(|e| &e.pos).chain(&gs.swarm) {
+            let arrow = if e.y == 0 {
+                '▲'
+            } else if e.y > h {
+                '▼'
+            } else if e.x == 0 {
+                '◀'
+            } else if e.x > w {
+                '▶'
+            } else {
+                continue;
+            };
+            let edge = Pos {
+                x: e.x.clamp(1, w.max(1)),
+                y: e.y.clamp(1, h.max(1)),
+            };
+            plot(
+                &mut grid,
+                edge,
+                Layer::Marker,
+                (arrow, Style::default().fg(Color::LightRed)),
+            );
+        }
+    }
+
+    // Highlight the enemies the assisted shot would hit
+    for &(x, top) in &assist_paths {
+        let target = Pos { x, y: top - 1 };
+        let glyph = match gs.enemies.iter().find(|e| e.pos == target) {
+            Some(e) => e.kind.glyph(),
+            None if gs.swarm.contains(&target) => swarm_glyph,
+            None => continue,
+        };
+        plot(
+            &mut grid,
+            target,
+            Layer::Marker,
+            (
+                glyph,
+                Style::default()
+                    .fg(Color::Black)
+                    .bg(Color::LightYellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        );
+    }
+
+    // Draw the victory fireworks
+    if gs.victory_anim > 0 {
+        for (fw, _) in &gs.fireworks {
+            // Negative coordinates saturate to the border, which plot skips
+            let pos = Pos {
+                x: fw.x.round() as u16,
+                y: fw.y.round() as u16,
+            };
+            let glyph = if fw.rocket { '|' } else { '*' };
+            plot(
+                &mut grid,
+                pos,
+                Layer::Effect,
+                (
+                    glyph,
+                    Style::default().fg(fw.color).add_modifier(Modifier::BOLD),
+                ),
+            );
+        }
+    }
+
+    // Draw sparks where bullets cancelled out
+    for (spark, _) in &gs.sparks {
+        plot(
+            &mut grid,
+            *spark,
+            Layer::Effect,
+            ('✶', Style::default().fg(Color::LightYellow)),
+        );
+    }
+
+    // Draw enemy bullets
+    for b in &gs.enemy_bullets {
+        plot(
+            &mut grid,
+            b.pos,
+            Layer::EnemyBullet,
+            (
+                cfg.enemy_bullet_glyph,
+                Style::default().fg(cfg.enemy_bullet_color),
+            ),
+        );
+    }
+
+    // Draw bullets
+    for b in &gs.bullets {
+        // Upgraded shots get their own glyph and color
+        let (glyph, tier_color) = match b.tier {
+            1 => ('|', Color::Yellow),
+            2 => ('!', Color::LightYellow),
+            _ => ('¦', Color::LightMagenta),
+        };
+        let color = bullet_color(gs, cfg, tier_color);
+        // Bullets that will punch through another blocking enemy get a doubled stroke
+        let glyph = if cfg.bullets_blocked_by_enemies && b.pierce > 1 {
+            '‖'
+        } else {
+            glyph
+        };
+        // Angled shots lean the way they travel on screen
+        let glyph = match (b.dx.signum(), cfg.mirror_render) {
+            (0, _) => glyph,
+            (-1, false) | (1, true) => '\\',
+            _ => '/',
+        };
+        plot(
+            &mut grid,
+            b.pos,
+            Layer::Bullet,
+            (
+                glyph,
+                Style::default().fg(color).add_modifier(Modifier::BOLD),
+            ),
+        );
+    }
+
+    // A fired beam fills the column above the ship it was fired from
+    if let Some((x, _)) = gs.beam {
+        for y in 1..gs.ship_row() {
+            plot(
+                &mut grid,
+                Pos { x, y },
+                Layer::Bullet,
+                (
+                    '┃',
+                    Style::default()
+                        .fg(Color::LightCyan)
+                        .add_modifier(Modifier::BOLD),
+                ),
+            );
+        }
+    }
+
+    // Draw the ships still in play.
+    // A freshly respawned ship blinks while it can't be hit, and the first glows while charging a beam.
+    for (i, ship) in gs.ships.iter().enumerate().filter(|(_, s)| s.in_play()) {
+        let charging = i == 0 && gs.beam_charge.is_some();
+        let color = if ship.invulnerable > 0 && (ship.invulnerable / 2) % 2 == 1 {
+            Color::DarkGray
+        } else if charging && gs.beam_charged(cfg) {
+            Color::White
+        } else if charging {
+            Color::LightBlue
+        } else if gs.buff_active(PowerUpKind::Shield) {
+            PowerUpKind::Shield.color()
+        } else {
+            SHIP_COLORS[i % SHIP_COLORS.len()]
+        };
+        plot(
+            &mut grid,
+            ship.pos,
+            Layer::Player,
+            ('^', Style::default().fg(color).add_modifier(Modifier::BOLD)),
+        );
+    }
+
+    // The ground is the top layer so nothing passing through its row hides it
+    if let Some((glyph, color)) = cfg.ground {
+        for x in 1..=inner.width {
+            plot(
+                &mut grid,
+                Pos { x, y: p.y + 1 },
+                Layer::Ground,
+                (glyph, Style::default().fg(color)),
+            );
+        }
+    }
+
+    // Mirroring is purely visual; the game keeps its unmirrored coordinates
+    if cfg.mirror_render {
+        for row in &mut grid {
+            row.reverse();
+        }
+    }
+
+    // Convert grid to styled text for ratatui Paragraph
+    let spans: Vec<Line> = grid
+        .iter()
+        .map(|row| {
+            Line::from(
+                row.iter()
+                    .map(|(c, s, _)| Span::styled(c.to_string(), *s))
+                    .collect::<Vec<_>>(),
+            )
+        })
+        .collect();
+
+    let play = Paragraph::new(spans).wrap(Wrap { trim: false });
+    f.render_widget(play, inner);
+
+    // Wave intro prompt across the middle of the board
+    if gs.spawn_anim > 0 || gs.awaiting_ready {
+        let prompt = if gs.awaiting_ready {
+            format!(
+                " Wave {} - press {} when ready ",
+                gs.level,
+                cfg.keys_for(Action::Shoot)
+            )
+        } else {
+            format!(" Wave {} - get ready ", gs.level)
+        };
+        let width = (prompt.chars().count() as u16).min(inner.width);
+        let rect = Rect {
+            x: inner.x + (inner.width - width) / 2,
+            y: inner.y + inner.height / 2,
+            width,
+            height: 1.min(inner.height),
+        };
+        f.render_widget(
+            Paragraph::new(prompt).style(
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            rect,
+        );
+    }
+
+    // Wave-complete interlude with the cleared wave's accuracy bonus
+    if gs.wave_clear_anim > 0 {
+        let mut lines = vec![format!(" Wave {} complete ", gs.level)];
+        if let Some((pct, bonus)) = gs.wave_accuracy {
+            lines.push(format!(" Accuracy {pct}% +{bonus} "));
+        }
+        for (i, line) in lines.into_iter().enumerate() {
+            let width = (line.chars().count() as u16).min(inner.width);
+            let y = inner.height / 2 + i as u16;
+            if y >= inner.height {
+                break;
+            }
+            let rect = Rect {
+                x: inner.x + (inner.width - width) / 2,
+                y: inner.y + y,
+                width,
+                height: 1,
+            };
+            f.render_widget(
+                Paragraph::new(line).style(
+                    Style::default()
+                        .fg(Color::LightGreen)
+                        .add_modifier(Modifier::BOLD),
+                ),
+                rect,
+            );
+        }
+    }
+}
+
+// Split the terminal into header, playfield panel and info panel
+fn screen_areas(size: Rect) -> (Rect, Rect, Rect) {
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(10)].as_ref())
+        .split(size);
+    let bottom = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(75), Constraint::Percentage(25)].as_ref())
+        .split(chunks[1]);
+    (chunks[0], bottom[0], bottom[1])
+}
+
+// Center the playfield in its panel, no wider than max_play_width, or at
+// exactly the virtual board size when one is set
+fn letterbox(panel: Rect, cfg: &GameConfig) -> Rect {
+    if let Some((width, height)) = cfg.virtual_size {
+        let (width, height) = (width.min(panel.width), height.min(panel.height));
+        return Rect {
+            x: panel.x + (panel.width - width) / 2,
+            y: panel.y + (panel.height - height) / 2,
+            width,
+            height,
+        };
+    }
+    let width = match cfg.max_play_width {
+        Some(max) => panel.width.min(max),
+        None => panel.width,
+    };
+    Rect {
+        x: panel.x + (panel.width - width) / 2,
+        width,
+        ..panel
+    }
+}
+
+// Board dimensions for a terminal size; all game logic works in this space.
+// A virtual board keeps the same size whatever the terminal.
+fn board_size(width: u16, height: u16, cfg: &GameConfig) -> (u16, u16) {
+    if let Some(size) = cfg.virtual_size {
+        return size;
+    }
+    let (_, panel, _) = screen_areas(Rect::new(0, 0, width, height));
+    let board = letterbox(panel, cfg);
+    (board.width, board.height)
+}
+
+// Whether the terminal has room for the whole board
+fn board_fits(width: u16, height: u16, cfg: &GameConfig) -> bool {
+    let (_, panel, _) = screen_areas(Rect::new(0, 0, width, height));
+    cfg.virtual_size
+        .is_none_or(|(w, h)| panel.width >= w && panel.height >= h)
+}
+
+// Draw score, info panel, progress bar, etc.
+fn draw_ui<B: ratatui::backend::Backend>(
+    f: &mut ratatui::Frame<B>,
+    gs: &GameState,
+    cfg: &GameConfig,
+    stats: &Stats,
+    hud_hidden: bool,
+) {
+    let size = f.size();
+
+    let (header_area, panel_area, info_area) = screen_areas(size);
+    let board = letterbox(panel_area, cfg);
+
+    // A virtual board is never clipped; ask for a bigger terminal instead
+    if !board_fits(size.width, size.height, cfg) {
+        let (w, h) = board_size(size.width, size.height, cfg);
+        let msg = format!("Please enlarge the terminal to fit the {w}x{h} board");
+        f.render_widget(
+            Paragraph::new(msg)
+                .wrap(Wrap { trim: true })
+                .style(Style::default().fg(Color::Yellow)),
+            size,
+        );
+        return;
+    }
+
+    // Screenshot mode: just the playfield, in the same place so nothing in play shifts
+    if hud_hidden {
+        draw_game(f, board, gs, cfg);
+        return;
+    }
+
+    // Header: score and run status
+    let mut status = vec![
+        Span::raw(" Score: "),
+        Span::styled(
+            cfg.format_score(gs.score),
+            Style::default().fg(Color::Yellow),
+        ),
+        Span::raw(if gs.trainer.is_some() {
+            "  Targets: "
+        } else {
+            "  Enemies: "
+        }),
+        Span::styled(
+            gs.enemies_remaining().to_string(),
+            Style::default().fg(Color::Red),
+        ),
+        Span::raw(if cfg.endless { "  Wave: " } else { "  Level: " }),
+        Span::styled(gs.level.to_string(), Style::default().fg(Color::Green)),
+    ];
+    // Versus: whether the invaders' player can fire again
+    if gs.versus {
+        let (text, style) = if gs.versus_reload == 0 {
+            (
+                "ready",
+                Style::default()
+                    .fg(Color::LightRed)
+                    .add_modifier(Modifier::BOLD),
+            )
+        } else {
+            ("reloading", Style::default().fg(Color::DarkGray))
+        };
+        status.push(Span::raw("  Invader fire: "));
+        status.push(Span::styled(text, style));
+    }
+    // Time-attack countdown, turning red for the last ten seconds
+    if let Some(left) = gs.time_left_ms(cfg) {
+        let secs = left.div_ceil(1000);
+        let color = if secs <= 10 {
+            Color::LightRed
+        } else {
+            Color::White
+        };
+        status.push(Span::raw("  Time: "));
+        status.push(Span::styled(
+            format!("{}:{:02}", secs / 60, secs % 60),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        ));
+    }
+    // Runs started past level 1 or with challenges are flagged so their scores aren't mistaken for full runs
+    if gs.start_level > 1
+        || gs.difficulty != Difficulty::Normal
+        || !gs.challenges.is_empty()
+        || gs.mode_tag().is_some()
+        || gs.assisted
+    {
+        status.push(Span::styled(
+            format!("  [{}]", gs.run_tag()),
+            Style::default().fg(Color::Magenta),
+        ));
+    }
+    if let Some(level) = cfg.campaign_level(gs.level) {
+        status.push(Span::styled(
+            format!(
+                "  Campaign {}/{} {}",
+                gs.level,
+                cfg.campaign.len(),
+                level.name
+            ),
+            Style::default().fg(Color::LightGreen),
+        ));
+    }
+    if gs.sudden_death {
+        status.push(Span::styled(
+            "  SUDDEN DEATH",
+            Style::default()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED),
+        ));
+    }
+    if let Some(modifier) = gs.wave_modifier {
+        status.push(Span::styled(
+            format!("  {}", modifier.label()),
+            Style::default()
+                .fg(Color::LightCyan)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    // Active power-ups with the seconds they have left
+    for &(kind, ticks) in &gs.buffs {
+        let secs = (ticks * cfg.tick_ms).div_ceil(1000);
+        status.push(Span::styled(
+            format!("  {} {secs}s", kind.label()),
+            Style::default()
+                .fg(kind.color())
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    // Magazine rounds left, or a bar filling up while it reloads
+    if cfg.magazine_size > 0 {
+        const BAR: u64 = 6;
+        let mag = if gs.reload_ticks > 0 {
+            let total = cfg.reload_ticks.max(1);
+            let done = (BAR * (total - gs.reload_ticks.min(total)) / total) as usize;
+            format!(
+                "Reloading {}{}",
+                "▰".repeat(done),
+                "▱".repeat(BAR as usize - done)
+            )
+        } else {
+            format!("{}/{}", gs.ammo_in_mag, cfg.magazine_size)
+        };
+        status.push(Span::raw("  Mag: "));
+        status.push(Span::styled(mag, Style::default().fg(Color::LightYellow)));
+    }
+    // Running combo with a bar that shrinks as its window runs out
+    if gs.combo >= 2 && cfg.combo_window_ticks > 0 {
+        const BAR: u64 = 8;
+        let filled = (gs.combo_ticks * BAR).div_ceil(cfg.combo_window_ticks) as usize;
+        status.push(Span::styled(
+            format!("  Combo {} x{} ", gs.combo, gs.combo_multiplier()),
+            Style::default()
+                .fg(Color::LightMagenta)
+                .add_modifier(Modifier::BOLD),
+        ));
+        status.push(Span::styled(
+            format!(
+                "{}{}",
+                "█".repeat(filled),
+                "░".repeat(BAR as usize - filled)
+            ),
+            Style::default().fg(Color::LightMagenta),
+        ));
+    }
+    // Race against the best earlier run of the same kind
+    if let Some(lead) = stats.ghost_lead(gs) {
+        let (text, color) = if lead >= 0 {
+            (format!("  +{lead} ahead"), Color::LightGreen)
+        } else {
+            (format!("  {lead} behind"), Color::LightRed)
+        };
+        status.push(Span::styled(text, Style::default().fg(color)));
+    }
+    if gs.turbo_ticks > 0 {
+        status.push(Span::styled(
+            "  TURBO",
+            Style::default()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    // One ship icon per life, or a count once they no longer fit
+    let icons = |lives: usize| {
+        if lives <= MAX_LIFE_ICONS {
+            "^".repeat(lives)
+        } else {
+            format!("^ x{lives}")
+        }
+    };
+    // Co-op: each ship's own score, and its own lives unless they come from a shared pool
+    if gs.ships.len() > 1 {
+        for (i, ship) in gs.ships.iter().enumerate() {
+            let style = Style::default()
+                .fg(SHIP_COLORS[i % SHIP_COLORS.len()])
+                .add_modifier(Modifier::BOLD);
+            status.push(Span::raw(format!("  P{}: ", i + 1)));
+            status.push(Span::styled(cfg.format_score(ship.score), style));
+            if cfg.uses_lives() && !cfg.shared_lives {
+                let lives = if ship.in_play() {
+                    icons(ship.lives)
+                } else {
+                    "out".to_string()
+                };
+                status.push(Span::styled(format!(" {lives}"), style));
+            }
+        }
+    }
+    // Lives only matter when they can be lost
+    if cfg.uses_lives() && (gs.ships.len() == 1 || cfg.shared_lives) {
+        let lives = icons(gs.ships[0].lives);
+        status.push(Span::raw("  Lives: "));
+        status.push(Span::styled(
+            lives,
+            Style::default()
+                .fg(Color::Cyan)
+                .add_modifier(Modifier::BOLD),
+        ));
+    }
+    let score_text = Line::from(status);
+    let header =
+        Paragraph::new(score_text).block(Block::default().borders(Borders::ALL).title(" Status "));
+    f.render_widget(header, header_area);
+
+    // Dim the margins when the playfield is letterboxed narrower than its panel
+    if board.width < panel_area.width {
+        let margin = "░".repeat(panel_area.width as usize);
+        let fill: Vec<Line> = (0..panel_area.height)
+            .map(|_| Line::from(margin.as_str()))
+            .collect();
+        f.render_widget(
+            Paragraph::new(fill).style(Style::default().fg(Color::DarkGray)),
+            panel_area,
+        );
+    }
+    draw_game(f, board, gs, cfg);
+
+    // Info panel with progress bar
+    let info_block = Block::default().borders(Borders::ALL).title(" Info ");
+    f.render_widget(info_block, info_area);
+
+    let inner = Rect {
+        x: info_area.x + 1,
+        y: info_area.y + 1,
+        width: info_area.width.saturating_sub(2),
+        height: info_area.height.saturating_sub(2),
+    };
+
+    let info = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints(
+            [
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Length(1),
+                Constraint::Min(0),
+            ]
+            .as_ref(),
+        )
+        .split(inner);
+
+    // The progress bar turns into the boss's health bar during a boss wave
+    let g = match &gs.boss {
+        Some(boss) => Gauge::default()
+            .block(Block::default().borders(Borders::NONE))
+            .gauge_style(Style::default().fg(Color::LightMagenta))
+            .label(format!("BOSS {}/{}", boss.hp, boss.max_hp))
+            .ratio(boss.hp as f64 / boss.max_hp.max(1) as f64),
+        None => Gauge::default()
+            .block(Block::default().borders(Borders::NONE))
+            .gauge_style(Style::default().fg(Color::Green))
+            .ratio(gs.progress()),
+    };
+    f.render_widget(g, info[0]);
+
+    // Weapon heat per ship, turning red as it nears the top and flashing when a shot was refused
+    let count = gs.ships.len();
+    let heat_areas = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints(vec![Constraint::Ratio(1, count as u32); count])
+        .split(info[1]);
+    for (i, ship) in gs.ships.iter().enumerate() {
+        let heat_style = if gs.blocked_shot > 0 {
+            Style::default()
+                .fg(cfg.blocked_shot_color)
+                .add_modifier(Modifier::BOLD | Modifier::REVERSED)
+        } else if ship.overheated {
+            Style::default().fg(Color::LightRed)
+        } else if ship.heat * 3 >= MAX_HEAT * 2 {
+            Style::default().fg(Color::Yellow)
+        } else {
+            Style::default().fg(SHIP_COLORS[i % SHIP_COLORS.len()])
+        };
+        let label = match (ship.overheated, count) {
+            (true, 1) => "OVERHEATED".to_string(),
+            (false, 1) => "Heat".to_string(),
+            (true, _) => format!("P{} OVERHEATED", i + 1),
+            (false, _) => format!("P{} Heat", i + 1),
+        };
+        let heat = Gauge::default()
+            .block(Block::default().borders(Borders::NONE))
+            .gauge_style(heat_style)
+            .label(label)
+            .ratio(ship.heat as f64 / MAX_HEAT as f64);
+        f.render_widget(heat, heat_areas[i]);
+    }
+
+    // Beam charge while charging, otherwise its recharge
+    if cfg.beam_charge_ticks > 0 {
+        let (label, ratio, color) = match gs.beam_charge {
+            Some(charge) => (
+                if gs.beam_charged(cfg) {
+                    "Beam READY - release"
+                } else {
+                    "Beam charging"
+                },
+                charge.min(cfg.beam_charge_ticks) as f64 / cfg.beam_charge_ticks as f64,
+                Color::LightBlue,
+            ),
+            None if gs.beam_cooldown > 0 => (
+                "Beam recharging",
+                1.0 - gs.beam_cooldown as f64 / cfg.beam_cooldown_ticks.max(1) as f64,
+                Color::DarkGray,
+            ),
+            None => ("Beam ready", 1.0, Color::LightCyan),
+        };
+        let beam = Gauge::default()
+            .block(Block::default().borders(Borders::NONE))
+            .gauge_style(Style::default().fg(color))
+            .label(label)
+            .ratio(ratio.clamp(0.0, 1.0));
+        f.render_widget(beam, info[2]);
+    }
+
+    // Seed of the run, then controls built from the active key bindings
+    let mut controls = vec![
+        Line::from(vec![
+            Span::raw("Seed: "),
+            Span::styled(gs.seed.to_string(), Style::default().fg(Color::Magenta)),
+        ]),
+        Line::from(""),
+    ];
+    if cfg.smart_bombs > 0 {
+        let stock = "●".repeat(gs.bombs) + &"○".repeat(cfg.smart_bombs.saturating_sub(gs.bombs));
+        controls.insert(
+            1,
+            Line::from(vec![
+                Span::raw("Bombs: "),
+                Span::styled(stock, Style::default().fg(Color::LightRed)),
+            ]),
+        );
+    }
+
+    // Event log, newest first, fading as entries age
+    for (msg, ttl) in &gs.events {
+        let color = match *ttl {
+            t if t > EVENT_TTL_TICKS / 3 * 2 => Color::White,
+            t if t > EVENT_TTL_TICKS / 3 => Color::Gray,
+            _ => Color::DarkGray,
+        };
+        controls.push(Line::from(Span::styled(
+            msg.clone(),
+            Style::default().fg(color),
+        )));
+    }
+    if !gs.events.is_empty() {
+        controls.push(Line::from(""));
+    }
+
+    controls.push(Line::from(Span::styled(
+        "Controls",
+        Style::default().add_modifier(Modifier::BOLD),
+    )));
+    let diagonal = |a: &Action| matches!(a, Action::ShootLeft | Action::ShootRight);
+    for action in Action::ALL.into_iter().filter(|a| {
+        !a.menu_only()
+            && !a.developer()
+            && (cfg.diagonal_shots || !diagonal(a))
+            && (cfg.magazine_size > 0 || *a != Action::Reload)
+            && (cfg.smart_bombs > 0 || *a != Action::SmartBomb)
+            && (cfg.coop || cfg.versus || !a.second_player())
+    }) {
+        controls.push(Line::from(vec![
+            Span::raw(format!("{}: ", action.label())),
+            Span::styled(cfg.keys_for(action), Style::default().fg(Color::Yellow)),
+        ]));
+    }
+    if cfg.beam_charge_ticks > 0 {
+        controls.push(Line::from(vec![
+            Span::raw("Beam: "),
+            Span::styled(
+                format!("hold {}", cfg.keys_for(Action::Shoot)),
+                Style::default().fg(Color::Yellow),
+            ),
+        ]));
+    }
+    f.render_widget(Paragraph::new(controls), info[4]);
+
+    // Show game over / win overlay
+    if gs.ended() {
+        let msg = match (&gs.trainer, gs.victory) {
+            (Some(_), _) => "TRAINING DONE",
+            (None, true) => "YOU WIN!",
+            (None, false) if gs.time_up => "TIME UP",
+            (None, false) if gs.versus => "INVADERS WIN",
+            (None, false) => "GAME OVER",
+        };
+        let accuracy = gs.accuracy();
+        let mut lines = match &gs.trainer {
+            Some(trainer) => vec![
+                Line::from(format!("Targets hit: {}", gs.kills)),
+                Line::from(format!(
+                    "Average reaction: {} ms",
+                    trainer.average_reaction_ticks() * cfg.tick_ms
+                )),
+                Line::from(format!("Accuracy: {accuracy}% ({} shots)", gs.shots_fired)),
+                Line::from(""),
+            ],
+            None => vec![
+                Line::from(format!("Final score: {}", cfg.format_score(gs.score))),
+                Line::from(format!(
+                    "Best ({}): {}",
+                    gs.run_tag(),
+                    cfg.format_score(stats.best_score(&gs.run_tag()))
+                )),
+            ],
+        };
+        if cfg.end_summary && gs.trainer.is_none() {
+            let secs = gs.tick_count * cfg.tick_ms / 1000;
+            lines.extend([
+                Line::from(""),
+                Line::from(format!("Kills: {}", gs.kills)),
+                Line::from(format!("Accuracy: {accuracy}% ({} shots)", gs.shots_fired)),
+                Line::from(format!("Waves cleared: {}", gs.waves_cleared)),
+                Line::from(format!("Time played: {}:{:02}", secs / 60, secs % 60)),
+            ]);
+            if cfg.combo_window_ticks > 0 {
+                lines.push(Line::from(format!("Best combo: {}", gs.best_combo)));
+            }
+            if gs.new_best {
+                lines.push(Line::from(Span::styled(
+                    "NEW HIGH SCORE!",
+                    Style::default()
+                        .fg(Color::LightGreen)
+                        .add_modifier(Modifier::BOLD),
+                )));
+            }
+            lines.push(Line::from(""));
+        }
+        lines.push(Line::from(format!(
+            "{}: restart, {}: menu, {}: quit",
+            cfg.keys_for(Action::Restart),
+            cfg.keys_for(Action::Menu),
+            cfg.keys_for(Action::Quit)
+        )));
+        // Sized to its contents, shrinking with the terminal
+        let height = (lines.len() as u16 + 4).min(size.height);
+        let rect = Rect {
+            x: size.x + (size.width / 2).saturating_sub(20),
+            y: size.y + (size.height / 2).saturating_sub(height / 2),
+            width: 40.min(size.width),
+            height,
+        };
+        let block = Block::default().borders(Borders::ALL).title(Span::styled(
+            msg,
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        ));
+        f.render_widget(block, rect);
+        let info = Paragraph::new(lines);
+        f.render_widget(
+            info,
+            Rect {
+                x: rect.x + 1,
+                y: rect.y + 2,
+                width: rect.width.saturating_sub(2),
+                height: rect.height.saturating_sub(3),
+            },
+        );
+    }
+}
+
+// Small "paused" box over the middle of the screen
+fn draw_pause<B: ratatui::backend::Backend>(f: &mut ratatui::Frame<B>, cfg: &GameConfig) {
+    let size = f.size();
+    let rect = Rect {
+        x: size.x + (size.width / 2).saturating_sub(13),
+        y: size.y + (size.height / 2).saturating_sub(2),
+        width: 26.min(size.width),
+        height: 6.min(size.height),
+    };
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        " PAUSED ",
+        Style::default()
+            .fg(Color::Yellow)
+            .add_modifier(Modifier::BOLD),
+    ));
+    let text = Paragraph::new(vec![
+        Line::from(format!(" {}: resume", cfg.keys_for(Action::Pause))),
+        Line::from(format!(" {}: restart", cfg.keys_for(Action::Restart))),
+        Line::from(format!(" {}: settings", cfg.keys_for(Action::Settings))),
+        Line::from(format!(" {}: quit", cfg.keys_for(Action::Quit))),
+    ]);
+    f.render_widget(Clear, rect);
+    f.render_widget(text.block(block), rect);
+}
+
+// Step-mode banner in the top-right corner, showing the current tick
+fn draw_step<B: ratatui::backend::Backend>(f: &mut ratatui::Frame<B>, cfg: &GameConfig, tick: u64) {
+    let size = f.size();
+    let width = 24.min(size.width);
+    let rect = Rect {
+        x: size.x + size.width - width,
+        y: size.y,
+        width,
+        height: 4.min(size.height),
+    };
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        " STEP MODE ",
+        Style::default()
+            .fg(Color::LightMagenta)
+            .add_modifier(Modifier::BOLD),
+    ));
+    let text = Paragraph::new(vec![
+        Line::from(Span::styled(
+            format!(" Tick {tick}"),
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD),
+        )),
+        Line::from(format!(
+            " {}: step, {}: run",
+            cfg.keys_for(Action::DebugStep),
+            cfg.keys_for(Action::DebugStepMode)
+        )),
+    ]);
+    f.render_widget(Clear, rect);
+    f.render_widget(text.block(block), rect);
+}
+
+// Draw the start menu with level selection and challenge checklist
+fn draw_menu<B: ratatui::backend::Backend>(
+    f: &mut ratatui::Frame<B>,
+    cfg: &GameConfig,
+    stats: &Stats,
+    menu: &MenuState,
+) {
+    let size = f.size();
+    let height = 15 + Challenge::ALL.len() as u16;
+    let rect = Rect {
+        x: size.x + (size.width / 2).saturating_sub(22),
+        y: size.y + (size.height / 2).saturating_sub(height / 2),
+        width: 44.min(size.width),
+        height: height.min(size.height),
+    };
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        " Space Invaders ",
+        Style::default()
+            .fg(Color::LightGreen)
+            .add_modifier(Modifier::BOLD),
+    ));
+    let selected = |row: usize| {
+        if menu.row == row {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        }
+    };
+    let mut lines = vec![
+        Line::from(""),
+        Line::from(vec![
+            Span::styled(" Start level: ", selected(0)),
+            Span::styled(
+                format!("< {} >", menu.start_level),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+            Span::raw(format!("  (max {})", stats.highest_level)),
+        ]),
+        Line::from(vec![
+            Span::styled(" Difficulty:  ", selected(1)),
+            Span::styled(
+                format!("< {} >", menu.difficulty.label()),
+                Style::default()
+                    .fg(Color::Yellow)
+                    .add_modifier(Modifier::BOLD),
+            ),
+        ]),
+        Line::from(""),
+        Line::from(" Challenges:"),
+    ];
+    for (i, challenge) in Challenge::ALL.into_iter().enumerate() {
+        let mark = if menu.challenges.contains(&challenge) {
+            "[x]"
+        } else {
+            "[ ]"
+        };
+        lines.push(Line::from(Span::styled(
+            format!("   {mark} {}", challenge.label()),
+            selected(i + 2),
+        )));
+    }
+    let tag = run_tag(
+        menu.start_level,
+        menu.difficulty,
+        &menu.challenges,
+        cfg.mode_tag(),
+        cfg.aim_assist,
+    );
+    lines.extend([
+        Line::from(""),
+        Line::from(format!(
+            " Best ({tag}): {}",
+            cfg.format_score(stats.best_score(&tag))
+        )),
+        Line::from(""),
+        Line::from(format!(
+            " {} / {}: select, {} / {}: change",
+            cfg.keys_for(Action::MenuUp),
+            cfg.keys_for(Action::MenuDown),
+            cfg.keys_for(Action::MoveLeft),
+            cfg.keys_for(Action::MoveRight)
+        )),
+        Line::from(format!(
+            " {}: start/toggle, {}: stats, {}: quit",
+            cfg.keys_for(Action::Shoot),
+            cfg.keys_for(Action::ViewStats),
+            cfg.keys_for(Action::Quit)
+        )),
+        Line::from(format!(
+            " {}: settings, {}: aim trainer",
+            cfg.keys_for(Action::Settings),
+            cfg.keys_for(Action::AimTrainer)
+        )),
+    ]);
+    f.render_widget(Paragraph::new(lines).block(block), rect);
+}
+
+// Draw the persisted stats, with a confirmation prompt before resetting
+fn draw_stats<B: ratatui::backend::Backend>(
+    f: &mut ratatui::Frame<B>,
+    cfg: &GameConfig,
+    stats: &Stats,
+    confirm_reset: bool,
+) {
+    let size = f.size();
+    let height = 9 + stats.best_scores.len() as u16;
+    let rect = Rect {
+        x: size.x + (size.width / 2).saturating_sub(20),
+        y: size.y + (size.height / 2).saturating_sub(height / 2),
+        width: 40.min(size.width),
+        height: height.min(size.height),
+    };
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        " Stats ",
+        Style::default()
+            .fg(Color::LightGreen)
+            .add_modifier(Modifier::BOLD),
+    ));
+    let mut lines = vec![
+        Line::from(format!(
+            " High score: {}",
+            cfg.format_score(stats.high_score())
+        )),
+        Line::from(format!(" Highest level: {}", stats.highest_level)),
+        Line::from(format!(" Games played: {}", stats.games_played)),
+        Line::from(""),
+    ];
+    for (tag, score) in &stats.best_scores {
+        lines.push(Line::from(format!(
+            "   Best {tag}: {}",
+            cfg.format_score(*score)
+        )));
+    }
+    lines.push(Line::from(""));
+    if confirm_reset {
+        lines.push(Line::from(Span::styled(
+            " Erase all stats? y: yes, any key: no",
+            Style::default()
+                .fg(Color::LightRed)
+                .add_modifier(Modifier::BOLD),
+        )));
+    } else {
+        lines.push(Line::from(format!(
+            " {}: reset stats, {}: back",
+            cfg.keys_for(Action::ResetStats),
+            cfg.keys_for(Action::Menu)
+        )));
+    }
+    f.render_widget(Paragraph::new(lines).block(block), rect);
+}
+
+fn draw_settings<B: ratatui::backend::Backend>(
+    f: &mut ratatui::Frame<B>,
+    cfg: &GameConfig,
+    row: usize,
+    from_pause: bool,
+) {
+    let size = f.size();
+    let developer: Vec<Action> = Action::ALL
+        .into_iter()
+        .filter(|a| a.developer() && cfg.dev_keys)
+        .collect();
+    let height = 9 + Setting::ALL.len() as u16 + developer.len() as u16;
+    let rect = Rect {
+        x: size.x + (size.width / 2).saturating_sub(20),
+        y: size.y + (size.height / 2).saturating_sub(height / 2),
+        width: 40.min(size.width),
+        height: height.min(size.height),
+    };
+    let block = Block::default().borders(Borders::ALL).title(Span::styled(
+        " Settings ",
+        Style::default()
+            .fg(Color::LightGreen)
+            .add_modifier(Modifier::BOLD),
+    ));
+    let mut lines = vec![Line::from("")];
+    for (i, setting) in Setting::ALL.into_iter().enumerate() {
+        let style = if i == row {
+            Style::default()
+                .fg(Color::Yellow)
+                .add_modifier(Modifier::BOLD)
+        } else {
+            Style::default()
+        };
+        let mut text = setting.row(cfg);
+        if from_pause && setting.next_run_only() {
+            text.push_str(" (next run)");
+        }
+        lines.push(Line::from(Span::styled(text, style)));
+    }
+    lines.extend([
+        Line::from(""),
+        Line::from(format!(
+            " {} / {}: select, {}: toggle",
+            cfg.keys_for(Action::MenuUp),
+            cfg.keys_for(Action::MenuDown),
+            cfg.keys_for(Action::Shoot)
+        )),
+        Line::from(format!(" {}: back", cfg.keys_for(Action::Menu))),
+        Line::from(""),
+    ]);
+    // Developer hotkeys are listed only while they're live
+    if developer.is_empty() {
+        lines.push(Line::from(Span::styled(
+            " Developer hotkeys off (--dev-keys)",
+            Style::default().fg(Color::DarkGray),
+        )));
+    } else {
+        lines.push(Line::from(" Developer hotkeys:"));
+        for action in developer {
+            lines.push(Line::from(vec![
+                Span::raw(format!("  {}: ", action.label())),
+                Span::styled(cfg.keys_for(action), Style::default().fg(Color::Yellow)),
+            ]));
+        }
+    }
+    f.render_widget(Paragraph::new(lines).block(block), rect);
+}
+
+// Parse a breach penalty given as "gameover", "life" or "points:<n>"
+fn parse_breach_penalty(value: &str) -> Result<BreachPenalty, String> {
+    match value {
+        "gameover" => Ok(BreachPenalty::GameOver),
+        "life" => Ok(BreachPenalty::Life),
+        _ => value
+            .strip_prefix("points:")
+            .and_then(|n| n.parse().ok())
+            .map(BreachPenalty::Points)
+            .ok_or_else(|| format!("invalid breach penalty: {value}")),
+    }
+}
+
+// Parse a sudden-death trigger given as "level:<n>" or "score:<n>"
+fn parse_sudden_death(value: &str) -> Result<SuddenDeath, String> {
+    let (kind, n) = value
+        .split_once(':')
+        .ok_or_else(|| format!("invalid sudden death trigger: {value}"))?;
+    let n = n
+        .parse()
+        .map_err(|_| format!("invalid sudden death trigger: {value}"))?;
+    match kind {
+        "level" => Ok(SuddenDeath::Level(n)),
+        "score" => Ok(SuddenDeath::Score(n)),
+        _ => Err(format!("invalid sudden death trigger: {value}")),
+    }
+}
+
+// Load a level table: one "<level> <fire every ticks> <move every ticks>" row per
+// line, sorted by level, with blank lines and lines starting with '#' ignored
+fn load_level_table(path: &str) -> Result<Vec<LevelRow>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+    let mut table: Vec<LevelRow> = Vec::new();
+    for (n, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || format!("{path}:{}: invalid level row: {line}", n + 1);
+        let fields: Vec<u64> = line
+            .split_whitespace()
+            .map(|f| f.parse().map_err(|_| invalid()))
+            .collect::<Result<_, _>>()?;
+        let &[level, fire_every_ticks, move_every_ticks] = fields.as_slice() else {
+            return Err(invalid());
+        };
+        let level = usize::try_from(level).map_err(|_| invalid())?;
+        if level == 0 || move_every_ticks == 0 {
+            return Err(invalid());
+        }
+        if table.last().is_some_and(|prev| prev.level >= level) {
+            return Err(format!(
+                "{path}:{}: levels must be in increasing order",
+                n + 1
+            ));
+        }
+        table.push(LevelRow {
+            level,
+            fire_every_ticks,
+            move_every_ticks,
+        });
+    }
+    if table.is_empty() {
+        return Err(format!("{path}: level table is empty"));
+    }
+    Ok(table)
+}
+
+// Load a campaign from a manifest listing one level file per line (relative to
+// the manifest, '#' comments allowed) or from a directory, whose files are
+// played in name order
+fn load_campaign(path: &str) -> Result<Vec<CampaignLevel>, String> {
+    let root = Path::new(path);
+    let files: Vec<PathBuf> = if root.is_dir() {
+        let mut files: Vec<PathBuf> = fs::read_dir(root)
+            .map_err(|e| format!("{path}: {e}"))?
+            .filter_map(|entry| entry.ok().map(|e| e.path()))
+            .filter(|p| p.is_file())
+            .filter(|p| {
+                !p.file_name()
+                    .is_some_and(|n| n.to_string_lossy().starts_with('.'))
+            })
+            .collect();
+        files.sort();
+        files
+    } else {
+        let text = fs::read_to_string(root).map_err(|e| format!("{path}: {e}"))?;
+        let dir = root.parent().unwrap_or(Path::new(""));
+        text.lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty() && !l.starts_with('#'))
+            .map(|l| dir.join(l))
+            .collect()
+    };
+    if files.is_empty() {
+        return Err(format!("{path}: campaign has no levels"));
+    }
+    files.iter().map(|f| load_campaign_level(f)).collect()
+}
+
+// Load one campaign level: optional "name <text>", "fire-every <ticks>" and
+// "move-every <ticks>" lines ('#' comments allowed), then a "formation" line
+// followed by the layout, one row per line, '#' marking an enemy:
+//
+//   name Checkerboard
+//   move-every 4
+//   formation
+//   # # # #
+//    # # #
+fn load_campaign_level(file: &Path) -> Result<CampaignLevel, String> {
+    let path = file.display();
+    let text = fs::read_to_string(file).map_err(|e| format!("{path}: {e}"))?;
+    let mut level = CampaignLevel {
+        name: String::new(),
+        formation: Vec::new(),
+        fire_every_ticks: None,
+        move_every_ticks: None,
+    };
+    let mut lines = text.lines().enumerate();
+    for (n, line) in lines.by_ref() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if line == "formation" {
+            break;
+        }
+        let invalid = || format!("{path}:{}: invalid level setting: {line}", n + 1);
+        let (key, value) = line.split_once(' ').ok_or_else(invalid)?;
+        let value = value.trim();
+        match key {
+            "name" => level.name = value.to_string(),
+            "fire-every" => level.fire_every_ticks = Some(value.parse().map_err(|_| invalid())?),
+            "move-every" => {
+                let ticks: u64 = value.parse().map_err(|_| invalid())?;
+                level.move_every_ticks = Some(ticks.max(1));
+            }
+            _ => return Err(invalid()),
+        }
+    }
+    for (row, (_, line)) in lines.enumerate() {
+        for (col, c) in line.chars().enumerate() {
+            if c == '#' {
+                level.formation.push(Pos {
+                    x: col as u16,
+                    y: row as u16,
+                });
+            }
+        }
+    }
+    if level.formation.is_empty() {
+        return Err(format!("{path}: level has no formation with enemies"));
+    }
+    Ok(level)
+}
+
+// Split an "http://host[:port][/path]" URL into a socket address and a request path
+fn parse_submit_url(url: &str) -> Result<(String, String), String> {
+    let rest = url
+        .strip_prefix("http://")
+        .ok_or_else(|| format!("only http:// URLs are supported: {url}"))?;
+    let (host, path) = rest.find('/').map_or((rest, "/"), |i| rest.split_at(i));
+    if host.is_empty() {
+        return Err(format!("invalid URL: {url}"));
+    }
+    let addr = if host.contains(':') {
+        host.to_string()
+    } else {
+        format!("{host}:80")
+    };
+    Ok((addr, path.to_string()))
+}
+
+// POST a JSON body to an HTTP endpoint, succeeding on any 2xx reply
+fn submit_result(url: &str, body: &str) -> Result<(), String> {
+    let (addr, path) = parse_submit_url(url)?;
+    let target = addr
+        .to_socket_addrs()
+        .map_err(|e| format!("{addr}: {e}"))?
+        .next()
+        .ok_or_else(|| format!("{addr}: no address found"))?;
+    let mut stream =
+        TcpStream::connect_timeout(&target, SUBMIT_TIMEOUT).map_err(|e| e.to_string())?;
+    stream
+        .set_read_timeout(Some(SUBMIT_TIMEOUT))
+        .and_then(|_| stream.set_write_timeout(Some(SUBMIT_TIMEOUT)))
+        .map_err(|e| e.to_string())?;
+    write!(
+        stream,
+        "POST {path} HTTP/1.1\r\nHost: {addr}\r\nContent-Type: application/json\r\n\
+         Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    )
+    .map_err(|e| e.to_string())?;
+    let mut status = String::new();
+    BufReader::new(stream)
+        .read_line(&mut status)
+        .map_err(|e| e.to_string())?;
+    match status.split_whitespace().nth(1) {
+        Some(code) if code.starts_with('2') => Ok(()),
+        _ => Err(format!("server replied {:?}", status.trim())),
+    }
+}
+
+// Parse a single-character glyph
+fn parse_glyph(value: &str) -> Result<char, String> {
+    let mut chars = value.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) => Ok(c),
+        _ => Err(format!("invalid glyph: {value}")),
+    }
+}
+
+// Parse a color name such as "red" or "light-blue"
+fn parse_color(name: &str) -> Result<Color, String> {
+    let color = match name {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" => Color::Gray,
+        "dark-gray" => Color::DarkGray,
+        "light-red" => Color::LightRed,
+        "light-green" => Color::LightGreen,
+        "light-yellow" => Color::LightYellow,
+        "light-blue" => Color::LightBlue,
+        "light-magenta" => Color::LightMagenta,
+        "light-cyan" => Color::LightCyan,
+        "white" => Color::White,
+        _ => return Err(format!("unknown color: {name}")),
+    };
+    Ok(color)
+}
+
+// Apply command-line overrides on top of the base config
+fn parse_args(cfg: &mut GameConfig, args: &[String]) -> Result<(), String> {
+    let mut args = args.iter().cloned();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--min-wave-enemies" => {
+                let v = value()?;
+                cfg.min_wave_enemies = v.parse().map_err(|_| format!("invalid count: {v}"))?;
+            }
+            "--lives" => {
+                let v = value()?;
+                cfg.starting_lives = v.parse().map_err(|_| format!("invalid lives: {v}"))?;
+            }
+            "--breach-penalty" => cfg.enemy_breach_penalty = parse_breach_penalty(&value()?)?,
+            "--breach-on-contact" => cfg.breach_on_contact = true,
+            "--sudden-death" => cfg.sudden_death = Some(parse_sudden_death(&value()?)?),
+            "--sudden-death-bonus" => {
+                let v = value()?;
+                cfg.sudden_death_bonus = v.parse().map_err(|_| format!("invalid points: {v}"))?;
+            }
+            "--seed" => {
+                let v = value()?;
+                cfg.seed = v.parse().map_err(|_| format!("invalid seed: {v}"))?;
+            }
+            "--random-colors" => cfg.randomize_enemy_colors = true,
+            "--dev-keys" => cfg.dev_keys = true,
+            "--debug" => {
+                cfg.dev_keys = true;
+                cfg.debug_step = true;
+            }
+            "--bind" => {
+                // --bind <action>=<key>[,<key>...] replaces the action's keys
+                let v = value()?;
+                let (name, keys) = v.split_once('=').ok_or(format!("invalid binding: {v}"))?;
+                let action = Action::ALL
+                    .into_iter()
+                    .find(|a| a.name() == name)
+                    .ok_or(format!("unknown action: {name}"))?;
+                let keys = keys
+                    .split(',')
+                    .map(parse_key)
+                    .collect::<Result<Vec<_>, _>>()?;
+                cfg.key_bindings
+                    .retain(|(key, a)| *a != action && !keys.contains(key));
+                cfg.key_bindings
+                    .extend(keys.into_iter().map(|key| (key, action)));
+            }
+            "--enemy-fire" => {
+                let v = value()?;
+                cfg.enemy_fire_every_ticks =
+                    v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--difficulty" => {
+                let v = value()?;
+                cfg.difficulty =
+                    Difficulty::from_tag(&v).ok_or_else(|| format!("unknown difficulty: {v}"))?;
+            }
+            "--fire-strategy" => {
+                cfg.fire_strategy = match value()?.as_str() {
+                    "random" => FireStrategy::Random,
+                    "round-robin" => FireStrategy::RoundRobin,
+                    "leftmost" => FireStrategy::Leftmost,
+                    "targeted" => FireStrategy::Targeted,
+                    other => return Err(format!("unknown fire strategy: {other}")),
+                }
+            }
+            "--enemy-bullet-pattern" => {
+                cfg.enemy_bullet_patterns = value()?
+                    .split(',')
+                    .map(|p| match p.trim() {
+                        "straight" => Ok(BulletPattern::Straight),
+                        "spread" => Ok(BulletPattern::Spread),
+                        "aimed" => Ok(BulletPattern::Aimed),
+                        other => Err(format!("unknown bullet pattern: {other}")),
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "--formations" => {
+                cfg.formations = value()?
+                    .split(',')
+                    .map(|f| match f.trim() {
+                        "grid" => Ok(Formation::Grid),
+                        "v" => Ok(Formation::V),
+                        "diamond" => Ok(Formation::Diamond),
+                        "checker" => Ok(Formation::Checker),
+                        other => Err(format!("unknown formation: {other}")),
+                    })
+                    .collect::<Result<_, _>>()?;
+            }
+            "--wave-modifiers" => {
+                let v = value()?;
+                cfg.wave_modifiers = if v == "all" {
+                    WaveModifier::ALL.to_vec()
+                } else {
+                    v.split(',')
+                        .map(|t| {
+                            WaveModifier::from_tag(t.trim())
+                                .ok_or_else(|| format!("unknown wave modifier: {t}"))
+                        })
+                        .collect::<Result<_, _>>()?
+                };
+            }
+            "--coop" => {
+                cfg.coop = true;
+                cfg.versus = false;
+            }
+            "--versus" => {
+                cfg.versus = true;
+                cfg.coop = false;
+            }
+            "--shared-lives" => cfg.shared_lives = true,
+            "--time-attack-secs" => {
+                let v = value()?;
+                let secs: u64 = v.parse().map_err(|_| format!("invalid duration: {v}"))?;
+                cfg.time_attack_secs = secs.max(1);
+            }
+            "--max-duration" => {
+                let v = value()?;
+                let secs = v.parse().map_err(|_| format!("invalid duration: {v}"))?;
+                cfg.max_duration_secs = Some(secs);
+            }
+            "--score-pad" => {
+                let v = value()?;
+                cfg.score_pad_width = v.parse().map_err(|_| format!("invalid width: {v}"))?;
+            }
+            "--score-separators" => cfg.score_thousands_sep = true,
+            "--swarm-every" => {
+                let v = value()?;
+                cfg.swarm_spawn_every_ticks =
+                    v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--boss-every" => {
+                let v = value()?;
+                cfg.boss_every = v.parse().map_err(|_| format!("invalid level count: {v}"))?;
+            }
+            "--boss-hp" => {
+                let v = value()?;
+                let hp: u32 = v.parse().map_err(|_| format!("invalid hp: {v}"))?;
+                cfg.boss_hp = hp.max(1);
+            }
+            "--dive-every" => {
+                let v = value()?;
+                cfg.dive_every_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--ufo" => {
+                let v = value()?;
+                cfg.ufo_every_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--asteroids" => {
+                let v = value()?;
+                cfg.asteroid_every_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--blocking-bullets" => cfg.bullets_blocked_by_enemies = true,
+            "--pierce" => {
+                let v = value()?;
+                let hits: u8 = v.parse().map_err(|_| format!("invalid hits: {v}"))?;
+                cfg.pierce_hits = hits.max(1);
+            }
+            "--scale-enemy-step" => cfg.scale_enemy_step = true,
+            "--virtual-size" => {
+                let v = value()?;
+                let size = v
+                    .split_once('x')
+                    .and_then(|(w, h)| Some((w.parse::<u16>().ok()?, h.parse::<u16>().ok()?)))
+                    .filter(|&(w, h)| w >= 10 && h >= 10)
+                    .ok_or(format!("invalid virtual size (WxH, at least 10x10): {v}"))?;
+                cfg.virtual_size = Some(size);
+            }
+            "--max-play-width" => {
+                let v = value()?;
+                let width: u16 = v.parse().map_err(|_| format!("invalid width: {v}"))?;
+                cfg.max_play_width = Some(width.max(10));
+            }
+            "--tick-ms" => {
+                let v = value()?;
+                cfg.tick_ms = v.parse().map_err(|_| format!("invalid tick_ms: {v}"))?;
+            }
+            "--point-decay-kills" => {
+                let v = value()?;
+                cfg.point_decay_per_wave_kill =
+                    v.parse().map_err(|_| format!("invalid decay: {v}"))?;
+            }
+            "--point-decay-ticks" => {
+                let v = value()?;
+                cfg.point_decay_every_ticks =
+                    v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--mirror-render" => cfg.mirror_render = true,
+            "--mono" => cfg.mono = true,
+            "--theme" => {
+                let v = value()?;
+                cfg.theme = Theme::from_tag(&v).ok_or_else(|| format!("unknown theme: {v}"))?;
+            }
+            "--wrap-ship" => cfg.wrap_ship = true,
+            "--keep-seed" => cfg.restart_same_seed = true,
+            "--formation-blocks-fire" => cfg.formation_blocks_fire = true,
+            "--bombs" => {
+                let v = value()?;
+                cfg.smart_bombs = v.parse().map_err(|_| format!("invalid count: {v}"))?;
+            }
+            "--bomb-reach" => {
+                let v = value()?;
+                cfg.bomb_reach = v.parse().map_err(|_| format!("invalid rows: {v}"))?;
+            }
+            "--magazine" => {
+                let v = value()?;
+                cfg.magazine_size = v.parse().map_err(|_| format!("invalid count: {v}"))?;
+            }
+            "--reload-ticks" => {
+                let v = value()?;
+                let ticks: u64 = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+                cfg.reload_ticks = ticks.max(1);
+            }
+            "--max-enemy-bullets" => {
+                let v = value()?;
+                cfg.max_enemy_bullets = v.parse().map_err(|_| format!("invalid count: {v}"))?;
+            }
+            "--enemy-bullet-glyph" => cfg.enemy_bullet_glyph = parse_glyph(&value()?)?,
+            "--ground" => cfg.ground = Some(cfg.ground.unwrap_or(('=', Color::DarkGray))),
+            "--ground-glyph" => {
+                let color = cfg.ground.map_or(Color::DarkGray, |(_, color)| color);
+                cfg.ground = Some((parse_glyph(&value()?)?, color));
+            }
+            "--ground-color" => {
+                let glyph = cfg.ground.map_or('=', |(glyph, _)| glyph);
+                cfg.ground = Some((glyph, parse_color(&value()?)?));
+            }
+            "--enemy-bullet-color" => cfg.enemy_bullet_color = parse_color(&value()?)?,
+            "--max-level" => {
+                let v = value()?;
+                let level: usize = v.parse().map_err(|_| format!("invalid level: {v}"))?;
+                cfg.max_level = Some(level.max(1));
+            }
+            "--player-step" => {
+                let v = value()?;
+                let step: u16 = v.parse().map_err(|_| format!("invalid step: {v}"))?;
+                cfg.player_step = step.max(1);
+            }
+            "--speed-up-every" => {
+                let v = value()?;
+                cfg.speed_up_every = v.parse().map_err(|_| format!("invalid level count: {v}"))?;
+            }
+            "--descent-growth" => {
+                let v = value()?;
+                cfg.descent_growth_every_bounces = v
+                    .parse()
+                    .map_err(|_| format!("invalid bounce count: {v}"))?;
+            }
+            "--descent-pause" => {
+                let v = value()?;
+                cfg.descent_pause_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--descent-squash" => {
+                let v = value()?;
+                cfg.descent_squash = v.parse().map_err(|_| format!("invalid rows: {v}"))?;
+            }
+            "--descent-squash-ticks" => {
+                let v = value()?;
+                let ticks: u64 = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+                cfg.descent_squash_ticks = ticks.max(1);
+            }
+            "--max-descent" => {
+                let v = value()?;
+                let rows: u16 = v.parse().map_err(|_| format!("invalid rows: {v}"))?;
+                cfg.max_descent = rows.max(1);
+            }
+            "--diagonal-shots" => cfg.diagonal_shots = true,
+            "--trainer-targets" => {
+                let v = value()?;
+                cfg.trainer_targets = v.parse().map_err(|_| format!("invalid count: {v}"))?;
+            }
+            "--trainer-speed" => {
+                let v = value()?;
+                cfg.trainer_target_move_every_ticks =
+                    v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--pause-on-focus-loss" => cfg.pause_on_focus_loss = true,
+            "--heat-per-shot" => {
+                let v = value()?;
+                cfg.heat_per_shot = v.parse().map_err(|_| format!("invalid heat: {v}"))?;
+            }
+            "--heat-cooling" => {
+                let v = value()?;
+                let heat: u32 = v.parse().map_err(|_| format!("invalid heat: {v}"))?;
+                cfg.heat_cooling = heat.max(1);
+            }
+            "--beam-charge" => {
+                let v = value()?;
+                cfg.beam_charge_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--beam-cooldown" => {
+                let v = value()?;
+                cfg.beam_cooldown_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--blocked-shot-color" => cfg.blocked_shot_color = parse_color(&value()?)?,
+            "--blocked-shot-bell" => cfg.blocked_shot_bell = true,
+            "--bullets-cancel" => cfg.bullets_cancel = true,
+            "--no-bullets-cancel" => cfg.bullets_cancel = false,
+            "--combo-window" => {
+                let v = value()?;
+                cfg.combo_window_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--combo-breaks-on-miss" => cfg.combo_breaks_on_miss = true,
+            "--shots-pass-shields" => cfg.shields_absorb_player_shots = false,
+            "--shields" => {
+                let v = value()?;
+                cfg.shields = v.parse().map_err(|_| format!("invalid count: {v}"))?;
+            }
+            "--shield-regen" => {
+                let v = value()?;
+                cfg.shield_regen = v.parse().map_err(|_| format!("invalid count: {v}"))?;
+            }
+            "--opening-volley" => {
+                let v = value()?;
+                cfg.opening_volley = v.parse().map_err(|_| format!("invalid count: {v}"))?;
+            }
+            "--powerup-chance" => {
+                let v = value()?;
+                let percent: u32 = v.parse().map_err(|_| format!("invalid percent: {v}"))?;
+                cfg.powerup_chance = percent.min(100);
+            }
+            "--powerup-ticks" => {
+                let v = value()?;
+                cfg.powerup_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--wave-clear-ticks" => {
+                let v = value()?;
+                cfg.wave_clear_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--accuracy-bonus" => {
+                let v = value()?;
+                cfg.accuracy_bonus = v.parse().map_err(|_| format!("invalid points: {v}"))?;
+            }
+            "--hitstop" => {
+                let v = value()?;
+                cfg.hitstop_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--simple-end-screen" => cfg.end_summary = false,
+            "--calm-enemies" => cfg.enemy_anger = false,
+            "--no-edge-markers" => cfg.edge_markers = false,
+            "--plain-bullets" => cfg.state_bullet_colors = false,
+            "--aim-assist" => cfg.aim_assist = true,
+            "--level-table" => cfg.level_table = load_level_table(&value()?)?,
+            "--campaign" => cfg.campaign = load_campaign(&value()?)?,
+            "--print-summary" => cfg.print_summary = true,
+            "--submit-url" => {
+                let url = value()?;
+                parse_submit_url(&url)?;
+                cfg.submit_url = Some(url);
+            }
+            "--panic" => {
+                let v = value()?;
+                cfg.enemy_panic_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--spawn-anim" | "--wave-intro-ticks" => {
+                let v = value()?;
+                cfg.spawn_anim_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--wave-intro-wait" => cfg.wave_intro_wait_for_key = true,
+            "--wave-intro-flash" => cfg.wave_intro_flash = Some(parse_color(&value()?)?),
+            "--wrap-enemies" => cfg.wrap_enemies = true,
+            "--wrap-descent-every" => {
+                let v = value()?;
+                cfg.wrap_descent_every =
+                    v.parse().map_err(|_| format!("invalid wrap count: {v}"))?;
+            }
+            "--invulnerable-ticks" => {
+                let v = value()?;
+                cfg.invulnerable_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--game-over-anim" => {
+                let v = value()?;
+                cfg.game_over_anim_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--victory-anim" => {
+                let v = value()?;
+                cfg.victory_anim_ticks = v.parse().map_err(|_| format!("invalid ticks: {v}"))?;
+            }
+            "--export-replay" => cfg.replay_export = Some(PathBuf::from(value()?)),
+            _ => return Err(format!("unknown argument: {arg}")),
+        }
+    }
+    Ok(())
+}
+
+// Top-level application: owns the game, its settings and the current screen,
+// and moves between screens in response to events and ticks
+struct App {
+    // Effective config for the current run: the base config plus any challenges
+    cfg: GameConfig,
+    base_cfg: GameConfig,
+    // Saved preferences, which only settings toggled in-game change
+    prefs: Prefs,
+    // Command-line settings, recorded into exported replays
+    args: Vec<String>,
+    gs: GameState,
+    stats: Stats,
+    screen: Screen,
+    menu: MenuState,
+    // The configured seed is used for the first run; later runs get fresh seeds
+    first_seed: Option<u64>,
+    // Replay being played back, with the index of its next input
+    playback: Option<(Replay, usize)>,
+    last_tick: Instant,
+    last_draw: Option<Instant>,
+    // Terminal size, to pause play while a virtual board doesn't fit
+    term_size: (u16, u16),
+    // Play is frozen; `auto_paused` marks a pause caused by focus loss, which
+    // regaining focus undoes
+    paused: bool,
+    auto_paused: bool,
+    // Draw only the playfield, for clean screenshots
+    hud_hidden: bool,
+    // Consecutive terminal errors shrugged off so far
+    io_failures: u32,
+    // Most recent debounced action and when its key was last seen
+    last_action: Option<(Action, Instant)>,
+    // Score submission still in flight, so a failure can be reported when it ends
+    submission: Option<JoinHandle<Result<(), String>>>,
+    // Debug stepping: play only advances when a single step is requested
+    step_mode: bool,
+    step_pending: bool,
+    quit: bool,
+}
+
+impl App {
+    fn new(
+        cfg: GameConfig,
+        prefs: Prefs,
+        args: Vec<String>,
+        playback: Option<(Replay, usize)>,
+        width: u16,
+        height: u16,
+    ) -> Self {
+        let (width, height) = board_size(width, height, &cfg);
+        let mut app = App {
+            gs: GameState::new(width, height, &cfg),
+            stats: Stats::load(),
+            screen: Screen::Menu,
+            menu: MenuState {
+                start_level: 1,
+                difficulty: cfg.difficulty,
+                row: 0,
+                challenges: Vec::new(),
+            },
+            first_seed: Some(cfg.seed),
+            step_mode: cfg.debug_step,
+            base_cfg: cfg.clone(),
+            cfg,
+            prefs,
+            args,
+            playback,
+            last_tick: Instant::now(),
+            last_draw: None,
+            term_size: (width, height),
+            paused: false,
+            auto_paused: false,
+            hud_hidden: false,
+            io_failures: 0,
+            last_action: None,
+            submission: None,
+            step_pending: false,
+            quit: false,
+        };
+        // A replay skips the menu and starts right away on its recorded board
+        if let Some((replay, _)) = &app.playback {
+            app.cfg.difficulty = replay.difficulty;
+            replay.difficulty.apply(&mut app.cfg);
+            for challenge in &replay.challenges {
+                challenge.apply(&mut app.cfg);
+            }
+            app.gs = GameState::new(replay.width, replay.height, &app.cfg);
+            app.gs.seed = replay.seed;
+            app.gs.start_level = replay.start_level;
+            app.gs.difficulty = replay.difficulty;
+            app.gs.challenges = replay.challenges.clone();
+            app.gs.reset(&app.cfg);
+            app.screen = Screen::Playing;
+        }
+        app
+    }
+
+    // Redraw at most once per frame so fast ticks can't starve input handling
+    fn frame_due(&self) -> bool {
+        self.last_draw.is_none_or(|t| t.elapsed() >= FRAME_TIME)
+    }
+
+    fn draw<B: ratatui::backend::Backend>(&mut self, f: &mut ratatui::Frame<B>) {
+        match self.screen {
+            Screen::Menu => draw_menu(f, &self.cfg, &self.stats, &self.menu),
+            Screen::Stats { confirm_reset } => draw_stats(f, &self.cfg, &self.stats, confirm_reset),
+            Screen::Settings { row, from_pause } => draw_settings(f, &self.cfg, row, from_pause),
+            Screen::Playing => draw_ui(f, &self.gs, &self.cfg, &self.stats, self.hud_hidden),
+        }
+        if self.paused && self.screen == Screen::Playing {
+            draw_pause(f, &self.cfg);
+        }
+        if self.step_mode && self.screen == Screen::Playing {
+            draw_step(f, &self.cfg, self.gs.tick_count);
+        }
+        if self.cfg.mono || self.cfg.theme != Theme::Classic {
+            let recolor = Recolor {
+                theme: self.cfg.theme,
+                mono: self.cfg.mono,
+            };
+            f.render_widget(recolor, f.size());
+        }
+        self.last_draw = Some(Instant::now());
+    }
+
+    // How long to wait for input before the next tick or frame is due
+    fn poll_timeout(&self) -> Duration {
+        self.gs
+            .tick_rate(&self.cfg)
+            .checked_sub(self.last_tick.elapsed())
+            .unwrap_or_else(|| Duration::from_millis(0))
+            .min(FRAME_TIME)
+    }
+
+    // Begin a run from the menu with the selected level and challenges
+    fn start_run(&mut self) {
+        self.cfg = self.base_cfg.clone();
+        self.cfg.difficulty = self.menu.difficulty;
+        self.cfg.difficulty.apply(&mut self.cfg);
+        for challenge in &self.menu.challenges {
+            challenge.apply(&mut self.cfg);
+        }
+        self.gs.seed = match self.first_seed.take() {
+            Some(seed) => seed,
+            None if self.cfg.restart_same_seed => self.gs.seed,
+            None => rand::random(),
+        };
+        self.gs.start_level = self.menu.start_level;
+        self.gs.difficulty = self.menu.difficulty;
+        self.gs.challenges = self.menu.challenges.clone();
+        self.gs.reset(&self.cfg);
+        self.screen = Screen::Playing;
+    }
+
+    // Close out a run: export its replay if requested, then fold it into the stats.
+    // Replays are only watched, never recorded.
+    fn end_run(&mut self) {
+        // Trainer sessions are practice and don't count either
+        if self.gs.run_recorded || self.playback.is_some() || self.gs.trainer.is_some() {
+            return;
+        }
+        if let Some(path) = &self.cfg.replay_export {
+            Replay::from_run(&self.gs, &self.args).save(path).ok();
+        }
+        self.stats.finish_run(&mut self.gs);
+        // Only finished runs are posted, not ones quit midway or cut off by the time limit.
+        // Posting happens in the background so a slow or missing server never holds up play or exit.
+        let finished = self.gs.game_over || self.gs.victory;
+        if let Some(url) = self.cfg.submit_url.clone().filter(|_| finished) {
+            let body = self.gs.result_json();
+            self.submission = Some(std::thread::spawn(move || submit_result(&url, &body)));
+        }
+    }
+
+    // Give a submission still in flight a bounded wait before exit, warning if it
+    // fails or doesn't finish in time
+    fn finish_submission(&mut self) {
+        let Some(handle) = self.submission.take() else {
+            return;
+        };
+        let deadline = Instant::now() + SUBMIT_TIMEOUT;
+        while !handle.is_finished() && Instant::now() < deadline {
+            std::thread::sleep(FRAME_TIME);
+        }
+        if !handle.is_finished() {
+            eprintln!("warning: score submission timed out");
+        } else if let Ok(Err(e)) = handle.join() {
+            eprintln!("warning: score submission failed: {e}");
+        }
+    }
+
+    // Note a transient terminal error in the event log and back off briefly;
+    // fatal errors, or too many transient ones in a row, are passed on
+    fn recover(&mut self, e: io::Error) -> io::Result<()> {
+        self.io_failures += 1;
+        if !is_transient(&e) || self.io_failures > MAX_IO_RETRIES {
+            return Err(e);
+        }
+        self.gs.log_event(format!("Terminal error: {e}"));
+        std::thread::sleep(FRAME_TIME);
+        Ok(())
+    }
+
+    // Flip a setting for the current run and later ones, and save it
+    fn toggle_setting(&mut self, setting: Setting) {
+        setting.advance(&mut self.cfg);
+        setting.copy(&self.cfg, &mut self.base_cfg);
+        self.prefs.update(setting, &self.cfg);
+        self.prefs.save().ok();
+        // Turning the assist on from the pause overlay still scores the run as assisted
+        if setting == Setting::AimAssist && self.cfg.aim_assist {
+            self.gs.assisted = true;
+        }
+    }
+
+    // A run is in progress, possibly with its settings open from the pause overlay
+    fn in_run(&self) -> bool {
+        matches!(
+            self.screen,
+            Screen::Playing
+                | Screen::Settings {
+                    from_pause: true,
+                    ..
+                }
+        )
+    }
+
+    // Carry out a developer hotkey. Leaving a run mid-game closes it out like quitting
+    // does, and a run that was tampered with is never recorded.
+    fn developer_action(&mut self, action: Action) {
+        let playing = self.in_run();
+        if playing
+            && matches!(
+                action,
+                Action::GoToMenu | Action::GoToSettings | Action::GoToStats
+            )
+        {
+            self.end_run();
+        }
+        match action {
+            Action::GoToMenu => self.screen = Screen::Menu,
+            Action::GoToGame if !playing => self.start_run(),
+            Action::GoToSettings => {
+                self.screen = Screen::Settings {
+                    row: 0,
+                    from_pause: false,
+                }
+            }
+            Action::GoToStats => {
+                self.screen = Screen::Stats {
+                    confirm_reset: false,
+                }
+            }
+            Action::DebugClearWave if playing && !self.gs.ended() => {
+                self.gs.run_recorded = true;
+                self.gs.enemies.clear();
+                self.gs.enemy_targets.clear();
+                self.gs.boss = None;
+                self.gs.spawn_anim = 0;
+                self.gs.swarm.clear();
+                self.gs.log_event("Debug: wave cleared");
+            }
+            Action::DebugAddLife if playing && !self.gs.ended() => {
+                self.gs.run_recorded = true;
+                for ship in &mut self.gs.ships {
+                    ship.lives += 1;
+                }
+                self.gs.log_event("Debug: +1 life");
+            }
+            Action::DebugStep if self.step_mode => self.step_pending = true,
+            Action::DebugStepMode => self.step_mode = !self.step_mode,
+            _ => {}
+        }
+    }
+
+    // Whether a key press repeats the previous screen-changing action too quickly.
+    // Every press restarts the window, so a held key never fires again.
+    fn bounced(&mut self, ev: &Event) -> bool {
+        let Event::Key(KeyEvent { code, .. }) = ev else {
+            return false;
+        };
+        let Some(action) = self.cfg.action_for(*code).filter(|a| a.debounced()) else {
+            return false;
+        };
+        let now = Instant::now();
+        let repeat = self
+            .last_action
+            .is_some_and(|(last, at)| last == action && now - at < ACTION_DEBOUNCE);
+        self.last_action = Some((action, now));
+        repeat
+    }
+
+    fn handle_event(&mut self, ev: Event) {
+        if let Event::Resize(w, h) = ev {
+            self.term_size = (w, h);
+        }
+        if self.bounced(&ev) {
+            return;
+        }
+        let (gs, cfg) = (&mut self.gs, &self.cfg);
+        match ev {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char('c'),
+                modifiers: KeyModifiers::CONTROL,
+                ..
+            }) => self.quit = true,
+            // A replay plays itself; only quitting is possible
+            Event::Key(KeyEvent { code, .. })
+                if self.playback.is_some() && cfg.action_for(code) == Some(Action::Quit) =>
+            {
+                self.quit = true
+            }
+            Event::Key(_) | Event::Resize(..) if self.playback.is_some() => {}
+            Event::Key(KeyEvent { code, .. })
+                if cfg.action_for(code).is_some_and(|a| a.developer()) =>
+            {
+                if let Some(action) = cfg.action_for(code).filter(|_| cfg.dev_keys) {
+                    self.developer_action(action);
+                }
+            }
+            Event::FocusLost
+                if cfg.pause_on_focus_loss && self.screen == Screen::Playing && !gs.ended() =>
+            {
+                self.auto_paused = !self.paused;
+                self.paused = true;
+            }
+            Event::FocusGained if self.auto_paused => {
+                self.paused = false;
+                self.auto_paused = false;
+            }
+            Event::Key(KeyEvent { code, .. }) if self.screen == Screen::Menu => {
+                let menu = &mut self.menu;
+                match cfg.action_for(code) {
+                    Some(Action::Quit) => self.quit = true,
+                    Some(Action::MenuUp) => menu.row = menu.row.saturating_sub(1),
+                    Some(Action::MenuDown) => {
+                        menu.row = (menu.row + 1).min(Challenge::ALL.len() + 1)
+                    }
+                    Some(Action::MoveLeft) if menu.row == 1 => {
+                        menu.difficulty = menu.difficulty.step(-1)
+                    }
+                    Some(Action::MoveRight) if menu.row == 1 => {
+                        menu.difficulty = menu.difficulty.step(1)
+                    }
+                    Some(Action::MoveLeft) => menu.start_level = (menu.start_level - 1).max(1),
+                    Some(Action::MoveRight) => {
+                        menu.start_level = (menu.start_level + 1).min(self.stats.highest_level)
+                    }
+                    Some(Action::Shoot) if menu.row > 1 => {
+                        menu.toggle_challenge(Challenge::ALL[menu.row - 2])
+                    }
+                    Some(Action::Shoot) => self.start_run(),
+                    Some(Action::ViewStats) => {
+                        self.screen = Screen::Stats {
+                            confirm_reset: false,
+                        }
+                    }
+                    Some(Action::Settings) => {
+                        self.screen = Screen::Settings {
+                            row: 0,
+                            from_pause: false,
+                        }
+                    }
+                    Some(Action::AimTrainer) => {
+                        self.cfg = self.base_cfg.clone();
+                        self.gs.seed = rand::random();
+                        self.gs.start_trainer(&self.cfg);
+                        self.screen = Screen::Playing;
+                    }
+                    _ => {}
+                }
+            }
+            Event::Key(KeyEvent { code, .. }) if matches!(self.screen, Screen::Settings { .. }) => {
+                let Screen::Settings { row, from_pause } = self.screen else {
+                    return;
+                };
+                match cfg.action_for(code) {
+                    Some(Action::Quit) => self.quit = true,
+                    Some(Action::MenuUp) => {
+                        self.screen = Screen::Settings {
+                            row: row.saturating_sub(1),
+                            from_pause,
+                        }
+                    }
+                    Some(Action::MenuDown) => {
+                        self.screen = Screen::Settings {
+                            row: (row + 1).min(Setting::ALL.len() - 1),
+                            from_pause,
+                        }
+                    }
+                    Some(Action::Shoot) => self.toggle_setting(Setting::ALL[row]),
+                    Some(Action::Menu) if from_pause => self.screen = Screen::Playing,
+                    Some(Action::Menu) => self.screen = Screen::Menu,
+                    _ => {}
+                }
+            }
+            // Clearing stats needs an explicit 'y'; anything else cancels
+            Event::Key(KeyEvent { code, .. })
+                if matches!(
+                    self.screen,
+                    Screen::Stats {
+                        confirm_reset: true
+                    }
+                ) =>
+            {
+                if code == KeyCode::Char('y') {
+                    self.stats.clear().ok();
+                    self.menu.start_level = 1;
+                }
+                self.screen = Screen::Stats {
+                    confirm_reset: false,
+                };
+            }
+            Event::Key(KeyEvent { code, .. }) if self.screen != Screen::Playing => {
+                match cfg.action_for(code) {
+                    Some(Action::Quit) => self.quit = true,
+                    Some(Action::ResetStats) => {
+                        self.screen = Screen::Stats {
+                            confirm_reset: true,
+                        }
+                    }
+                    Some(Action::Menu) => self.screen = Screen::Menu,
+                    _ => {}
+                }
+            }
+            Event::Key(KeyEvent { code, .. }) => match cfg.play_action_for(code) {
+                Some(Action::Quit) => self.quit = true,
+                Some(Action::Pause) if !gs.ended() => {
+                    self.paused = !self.paused;
+                    self.auto_paused = false;
+                }
+                // Restarting from the pause overlay abandons the run in progress
+                Some(Action::Restart) if self.paused => {
+                    self.paused = false;
+                    self.auto_paused = false;
+                    if gs.trainer.is_some() {
+                        gs.start_trainer(cfg);
+                    } else {
+                        if !cfg.restart_same_seed {
+                            gs.seed = rand::random();
+                        }
+                        gs.reset(cfg);
+                    }
+                }
+                // Settings is a menu key, so it is looked up outside the play bindings
+                _ if self.paused && cfg.action_for(code) == Some(Action::Settings) => {
+                    self.screen = Screen::Settings {
+                        row: 0,
+                        from_pause: true,
+                    }
+                }
+                // Nothing else reaches a paused game
+                _ if self.paused => {}
+                // Any other key skips the game-over animation
+                _ if gs.game_over_anim > 0 => gs.game_over_anim = 0,
+                _ if gs.victory_anim > 0 => gs.victory_anim = 0,
+                Some(Action::MoveLeft) if cfg.mirror_controls => {
+                    gs.apply_input(ReplayInput::Right, cfg)
+                }
+                Some(Action::MoveRight) if cfg.mirror_controls => {
+                    gs.apply_input(ReplayInput::Left, cfg)
+                }
+                Some(Action::MoveLeft) => gs.apply_input(ReplayInput::Left, cfg),
+                Some(Action::MoveRight) => gs.apply_input(ReplayInput::Right, cfg),
+                Some(Action::P2Left) if cfg.mirror_controls => {
+                    gs.apply_input(ReplayInput::P2Right, cfg)
+                }
+                Some(Action::P2Right) if cfg.mirror_controls => {
+                    gs.apply_input(ReplayInput::P2Left, cfg)
+                }
+                Some(Action::P2Left) => gs.apply_input(ReplayInput::P2Left, cfg),
+                Some(Action::P2Right) => gs.apply_input(ReplayInput::P2Right, cfg),
+                Some(Action::P2Shoot)
+                    if !gs.game_over && !gs.victory && gs.spawn_anim == 0 && !gs.awaiting_ready =>
+                {
+                    gs.apply_input(ReplayInput::P2Shoot, cfg)
+                }
+                Some(Action::Restart) | Some(Action::RestartSameSeed)
+                    if gs.ended() && gs.trainer.is_some() =>
+                {
+                    gs.start_trainer(cfg)
+                }
+                Some(Action::Restart) if gs.ended() => {
+                    if !cfg.restart_same_seed {
+                        gs.seed = rand::random();
+                    }
+                    gs.reset(cfg)
+                }
+                Some(Action::RestartSameSeed) if gs.ended() => gs.reset(cfg),
+                Some(Action::Menu) if gs.ended() => self.screen = Screen::Menu,
+                Some(Action::Turbo) if !gs.game_over && !gs.victory => {
+                    gs.apply_input(ReplayInput::Turbo, cfg)
+                }
+                Some(Action::ToggleReticle) => self.toggle_setting(Setting::Reticle),
+                Some(Action::ToggleHud) => self.hud_hidden = !self.hud_hidden,
+                // Shooting while a wave drops in just lands it
+                Some(Action::Shoot)
+                    if gs.spawn_anim > 0 || gs.awaiting_ready || gs.wave_clear_anim > 0 =>
+                {
+                    gs.apply_input(ReplayInput::SkipSpawn, cfg)
+                }
+                Some(Action::Shoot) if !gs.game_over && !gs.victory => {
+                    gs.apply_input(ReplayInput::Shoot, cfg);
+                    if cfg.blocked_shot_bell && gs.blocked_shot == BLOCKED_SHOT_TICKS {
+                        let mut out = io::stdout();
+                        let _ = out.write_all(b"\x07").and_then(|_| out.flush());
+                    }
+                }
+                Some(Action::Reload) if cfg.magazine_size > 0 && !gs.game_over && !gs.victory => {
+                    gs.apply_input(ReplayInput::Reload, cfg)
+                }
+                Some(Action::SmartBomb) if gs.bombs > 0 && !gs.game_over && !gs.victory => {
+                    gs.apply_input(ReplayInput::Bomb, cfg)
+                }
+                Some(Action::ShootLeft) | Some(Action::ShootRight)
+                    if cfg.diagonal_shots && !gs.game_over && !gs.victory =>
+                {
+                    // Mirrored controls flip the shot's angle along with movement
+                    let left = (cfg.play_action_for(code) == Some(Action::ShootLeft))
+                        != cfg.mirror_controls;
+                    let input = if left {
+                        ReplayInput::ShootLeft
+                    } else {
+                        ReplayInput::ShootRight
+                    };
+                    gs.apply_input(input, cfg)
+                }
+                _ => {}
+            },
+            Event::Resize(w, h) => {
+                let (w, h) = board_size(w, h, cfg);
+                gs.apply_input(ReplayInput::Resize(w, h), cfg);
+            }
+            _ => {}
+        }
+    }
+
+    // Tick game logic at a fixed interval
+    fn update(&mut self) {
+        let finished = self.submission.take_if(|h| h.is_finished());
+        if let Some(Err(e)) = finished.and_then(|h| h.join().ok()) {
+            self.gs.log_event(format!("Score submission failed: {e}"));
+        }
+        // A requested step runs at once instead of waiting for the tick rate
+        if !self.step_pending && self.last_tick.elapsed() < self.gs.tick_rate(&self.cfg) {
+            return;
+        }
+        if let Some((replay, next)) = &mut self.playback {
+            // Feed recorded inputs made before this tick
+            while let Some(&(_, input)) = replay
+                .inputs
+                .get(*next)
+                .filter(|(t, _)| *t <= self.gs.tick_count)
+            {
+                self.gs.apply_input(input, &self.cfg);
+                *next += 1;
+            }
+        }
+        // Play holds still while the terminal is too small to show the board
+        let (w, h) = self.term_size;
+        let live = self.screen == Screen::Playing && !self.paused && board_fits(w, h, &self.cfg);
+        if live && (!self.step_mode || std::mem::take(&mut self.step_pending)) {
+            let gs = &mut self.gs;
+            gs.live_ms += gs.tick_rate(&self.cfg).as_millis() as u64;
+            gs.tick(&self.cfg);
+            if gs.kills > 0 && gs.kills.is_multiple_of(self.cfg.enemy_speedup_every_kills) {
+                gs.enemy_move_every_ticks = gs.enemy_move_every_ticks.saturating_sub(1).max(1);
+            }
+            if gs.game_over || gs.victory {
+                self.end_run();
+            }
+            // Unattended displays hand back to the menu after the (real) time limit
+            if self.playback.is_none()
+                && self
+                    .cfg
+                    .max_duration_secs
+                    .is_some_and(|max| self.gs.live_ms >= max * 1000)
+            {
+                self.end_run();
+                self.screen = Screen::Menu;
+            }
+        }
+        self.last_tick = Instant::now();
+    }
+}
+
+// Main event loop; only fatal terminal errors end it early
+fn run(terminal: &mut Terminal<CrosstermBackend<io::Stdout>>, app: &mut App) -> io::Result<()> {
+    while !app.quit {
+        let drawn = if app.frame_due() {
+            terminal.draw(|f| app.draw(f)).map(drop)
+        } else {
+            Ok(())
+        };
+        if let Err(e) = drawn {
+            app.recover(e)?;
+        }
+
+        let events = match read_events(app.poll_timeout()) {
+            Ok(events) => {
+                app.io_failures = 0;
+                events
+            }
+            Err(e) => {
+                app.recover(e)?;
+                Vec::new()
+            }
+        };
+        for ev in events {
+            app.handle_event(ev);
+            if app.quit {
+                break;
+            }
+        }
+
+        if !app.quit {
+            app.update();
+        }
+    }
+    Ok(())
+}
+
+// Drain every pending event, not just one per tick
+fn read_events(timeout: Duration) -> io::Result<Vec<Event>> {
+    let mut events = Vec::new();
+    if event::poll(timeout)? {
+        events.push(event::read()?);
+        while event::poll(Duration::ZERO)? {
+            events.push(event::read()?);
+        }
+    }
+    Ok(events)
+}
+
+// Terminal errors that may clear up on their own, e.g. a signal interrupting a read
+fn is_transient(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+    )
+}
+
+// Base game settings, before prefs and command-line flags
+impl Default for GameConfig {
+    fn default() -> Self {
+        GameConfig {
+            tick_ms: 100,
+            initial_enemy_rows: 3,
+            initial_enemy_cols: 6,
+            min_wave_enemies: 0,
+            enemy_move_every_ticks: 6,
+            enemy_speedup_every_kills: 5,
+            starting_lives: 3,
+            enemy_breach_penalty: BreachPenalty::GameOver,
+            breach_on_contact: false,
+            sudden_death: None,
+            sudden_death_bonus: 5000,
+            seed: rand::random(),
+            randomize_enemy_colors: false,
+            game_over_anim_ticks: 10,
+            invulnerable_ticks: 15,
+            victory_anim_ticks: 30,
+            heat_per_shot: 30,
+            heat_cooling: 3,
+            beam_charge_ticks: 10,
+            beam_cooldown_ticks: 100,
+            magazine_size: 0,
+            reload_ticks: 20,
+            smart_bombs: 2,
+            bomb_reach: 8,
+            weapon_upgrade_scores: vec![300, 800],
+            key_bindings: default_key_bindings(),
+            dev_keys: false,
+            debug_step: false,
+            enemy_fire_every_ticks: 20,
+            fire_strategy: FireStrategy::Random,
+            max_duration_secs: None,
+            show_reticle: false,
+            score_pad_width: 0,
+            score_thousands_sep: false,
+            swarm_spawn_every_ticks: 60,
+            swarm_min_level: 3,
+            swarm_move_every_ticks: 2,
+            swarm_points: 15,
+            boss_every: 5,
+            boss_hp: 20,
+            dive_every_ticks: 120,
+            ufo_every_ticks: 400,
+            powerup_chance: 8,
+            powerup_ticks: 150,
+            asteroid_every_ticks: 250,
+            bullets_blocked_by_enemies: false,
+            pierce_hits: 1,
+            scale_enemy_step: false,
+            max_play_width: None,
+            turbo_hold_ticks: 8,
+            kill_points: 10,
+            kill_points_min: 2,
+            point_decay_per_wave_kill: 0,
+            point_decay_every_ticks: 0,
+            mirror_controls: false,
+            mirror_render: false,
+            mono: false,
+            theme: Theme::Classic,
+            wrap_ship: false,
+            restart_same_seed: false,
+            max_enemy_bullets: 4,
+            formation_blocks_fire: false,
+            enemy_bullet_patterns: vec![BulletPattern::Straight],
+            formations: vec![
+                Formation::Grid,
+                Formation::V,
+                Formation::Diamond,
+                Formation::Checker,
+            ],
+            enemy_bullet_glyph: '*',
+            enemy_bullet_color: Color::LightRed,
+            ground: None,
+            max_level: None,
+            endless: false,
+            coop: false,
+            versus: false,
+            shared_lives: false,
+            time_attack: false,
+            time_attack_secs: 120,
+            player_step: 1,
+            speed_up_every: 3,
+            descent_growth_every_bounces: 0,
+            max_descent: 3,
+            descent_pause_ticks: 0,
+            descent_squash: 0,
+            descent_squash_ticks: 2,
+            wrap_enemies: false,
+            wrap_descent_every: 1,
+            diagonal_shots: false,
+            blocked_shot_color: Color::LightRed,
+            blocked_shot_bell: false,
+            bullets_cancel: true,
+            combo_window_ticks: 0,
+            combo_breaks_on_miss: false,
+            opening_volley: 0,
+            shields: 4,
+            shield_regen: 0,
+            shields_absorb_player_shots: true,
+            hitstop_ticks: 3,
+            end_summary: true,
+            spawn_anim_ticks: 8,
+            wave_intro_wait_for_key: false,
+            wave_clear_ticks: 15,
+            accuracy_bonus: 500,
+            wave_intro_flash: None,
+            enemy_anger: true,
+            edge_markers: true,
+            state_bullet_colors: true,
+            aim_assist: false,
+            wave_modifiers: Vec::new(),
+            enemy_panic_ticks: 0,
+            level_table: Vec::new(),
+            campaign: Vec::new(),
+            difficulty: Difficulty::Normal,
+            submit_url: None,
+            print_summary: false,
+            virtual_size: None,
+            trainer_targets: 10,
+            trainer_target_move_every_ticks: 0,
+            pause_on_focus_loss: false,
+            replay_export: None,
+        }
+    }
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let mut cfg = GameConfig::default();
+    let prefs = Prefs::load();
+    prefs.apply(&mut cfg);
+    let mut args: Vec<String> = std::env::args().skip(1).collect();
+    // Playing a replay takes its settings from the file, not the command line
+    let playback = match args.iter().position(|a| a == "--replay") {
+        Some(i) => {
+            let path = args.get(i + 1).ok_or("missing value for --replay")?;
+            let replay = Replay::load(Path::new(path))?;
+            args = replay.args.clone();
+            // The saved preference doesn't apply; the replay's args say if the run wrapped
+            cfg.wrap_ship = false;
+            Some((replay, 0))
+        }
+        None => None,
+    };
+    parse_args(&mut cfg, &args)?;
+    // Settings recorded into exported replays (where a run is saved or sent is not a setting)
+    for flag in ["--export-replay", "--submit-url"] {
+        if let Some(i) = args.iter().position(|a| a == flag) {
+            args.drain(i..i + 2);
+        }
+    }
+    if cfg.tick_ms < MIN_TICK_MS {
+        eprintln!(
+            "warning: tick_ms {} is too fast to play, using {MIN_TICK_MS}",
+            cfg.tick_ms
+        );
+        cfg.tick_ms = MIN_TICK_MS;
+    }
+
+    // A panic restores the terminal before its message is printed, or the message
+    // lands garbled on the alternate screen and the shell is left in raw mode
+    let default_hook = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        disable_raw_mode().ok();
+        execute!(
+            io::stdout(),
+            LeaveAlternateScreen,
+            DisableMouseCapture,
+            DisableFocusChange,
+            cursor::Show
+        )
+        .ok();
+        default_hook(info);
+    }));
+
+    // Setup terminal in raw + alternate screen mode
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen, EnableMouseCapture)?;
+    // Terminals without focus reporting just never send focus events
+    if cfg.pause_on_focus_loss {
+        execute!(stdout, EnableFocusChange)?;
+    }
+    let backend = CrosstermBackend::new(stdout);
+    let mut terminal = Terminal::new(backend)?;
+    terminal.clear()?;
+
+    let size = terminal.size()?;
+    let mut app = App::new(cfg, prefs, args, playback, size.width, size.height);
+
+    let result = run(&mut terminal, &mut app);
+
+    // Keep progress from a run that was quit midway (or cut short by an error)
+    if app.in_run() {
+        app.end_run();
+    }
+
+    // Restore terminal before exiting, even when the loop failed
+    disable_raw_mode()?;
+    execute!(
+        terminal.backend_mut(),
+        LeaveAlternateScreen,
+        DisableMouseCapture,
+        DisableFocusChange
+    )?;
+    terminal.show_cursor()?;
+    app.finish_submission();
+    // Printed once the terminal is back to normal, and even if the game loop failed
+    if app.cfg.print_summary {
+        eprintln!("{}", app.gs.summary_line());
+    }
+    result?;
+    println!(
+        "Thanks for playing! Final score: {}",
+        app.cfg.format_score(app.gs.score)
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Fixed seed, with every spawner and timed effect off, so a test sets up only what it needs
+    fn quiet_config() -> GameConfig {
+        GameConfig {
+            seed: 7,
+            spawn_anim_ticks: 0,
+            enemy_fire_every_ticks: 0,
+            swarm_spawn_every_ticks: 0,
+            ufo_every_ticks: 0,
+            dive_every_ticks: 0,
+            powerup_chance: 0,
+            asteroid_every_ticks: 0,
+            shields: 0,
+            boss_every: 0,
+            hitstop_ticks: 0,
+            wave_clear_ticks: 0,
+            ..GameConfig::default()
+        }
+    }
+
+    // A 40x20 board with the formation cleared away
+    fn empty_board(cfg: &GameConfig) -> GameState {
+        let mut gs = GameState::new(40, 20, cfg);
+        gs.enemies.clear();
+        gs.enemy_targets.clear();
+        gs
+    }
+
+    fn bullet_columns(gs: &GameState) -> Vec<u16> {
+        let mut xs: Vec<u16> = gs.bullets.iter().map(|b| b.pos.x).collect();
+        xs.sort_unstable();
+        xs
+    }
+
+    // Put enemies on the given cells
+    fn place(gs: &mut GameState, cells: &[(u16, u16)]) {
+        for &(x, y) in cells {
+            gs.enemies
+                .push(Enemy::new(Pos { x, y }, EnemyKind::for_row(0)));
+        }
+    }
+
+    // A straight player shot at (x, y)
+    fn player_shot(cfg: &GameConfig, x: u16, y: u16) -> Bullet {
+        Bullet {
+            pos: Pos { x, y },
+            tier: 1,
+            dx: 0,
+            hit: false,
+            pierce: cfg.pierce_hits,
+            owner: 0,
+        }
+    }
+
+    // Fly a straight shot up from (x, y) for a few ticks, resolving hits after each move
+    fn fly_shot(gs: &mut GameState, cfg: &GameConfig, x: u16, y: u16, ticks: usize) {
+        gs.bullets.push(player_shot(cfg, x, y));
+        for _ in 0..ticks {
+            gs.move_bullets(cfg);
+            gs.resolve_hits(cfg);
+        }
+    }
+
+    // A scratch path under the system temp dir, unique to this test process
+    fn temp_path(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("space-invaders-test-{}-{name}", std::process::id()))
+    }
+
+    // A lone enemy against the right wall, one row above the ship, that drops into the
+    // ship's row on the next tick
+    fn about_to_breach(cfg: &GameConfig) -> GameState {
+        let mut gs = empty_board(cfg);
+        let row = gs.ship_row();
+        place(&mut gs, &[(37, row - 1)]);
+        gs.enemy_direction = 1;
+        gs
+    }
+
+    #[test]
+    fn weapon_level_sets_the_shot_pattern() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        let x = gs.ships[0].pos.x;
+        gs.shoot(&cfg, 0);
+        assert_eq!(bullet_columns(&gs), [x]);
+
+        gs.bullets.clear();
+        gs.ships[0].heat = 0;
+        gs.weapon_level = 2;
+        gs.shoot(&cfg, 0);
+        assert_eq!(bullet_columns(&gs), [x - 1, x + 1]);
+
+        gs.bullets.clear();
+        gs.ships[0].heat = 0;
+        gs.weapon_level = 3;
+        gs.shoot(&cfg, 0);
+        assert_eq!(bullet_columns(&gs), [x - 1, x, x + 1]);
+        assert!(
+            gs.bullets
+                .iter()
+                .all(|b| b.tier == 3 && b.pos.y == gs.ships[0].pos.y - 1)
+        );
+    }
+
+    #[test]
+    fn fire_strategies_pick_the_expected_shooter() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        // Three columns, the middle one two deep
+        place(&mut gs, &[(5, 3), (10, 3), (10, 4), (15, 3)]);
+        let leaders = [Pos { x: 5, y: 3 }, Pos { x: 10, y: 4 }, Pos { x: 15, y: 3 }];
+
+        assert_eq!(gs.choose_shooter(FireStrategy::Leftmost), Some(leaders[0]));
+        assert_eq!(gs.choose_shooter(FireStrategy::Leftmost), Some(leaders[0]));
+
+        let cycle: Vec<_> = (0..4)
+            .map(|_| gs.choose_shooter(FireStrategy::RoundRobin))
+            .collect();
+        assert_eq!(
+            cycle,
+            [
+                Some(leaders[0]),
+                Some(leaders[1]),
+                Some(leaders[2]),
+                Some(leaders[0])
+            ]
+        );
+
+        // Random only ever picks a column's front enemy
+        for _ in 0..50 {
+            let shooter = gs.choose_shooter(FireStrategy::Random).unwrap();
+            assert!(leaders.contains(&shooter));
+        }
+
+        gs.enemies.clear();
+        assert_eq!(gs.choose_shooter(FireStrategy::Leftmost), None);
+    }
+
+    #[test]
+    fn bullets_pass_through_stacked_enemies_by_default() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(10, 4), (10, 5)]);
+        fly_shot(&mut gs, &cfg, 10, 7, 3);
+        assert!(gs.enemies.is_empty());
+        assert_eq!(gs.kills, 2);
+    }
+
+    #[test]
+    fn blocking_bullets_only_destroy_the_front_enemy() {
+        let cfg = GameConfig {
+            bullets_blocked_by_enemies: true,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(10, 4), (10, 5)]);
+        fly_shot(&mut gs, &cfg, 10, 7, 3);
+        assert_eq!(gs.enemies.len(), 1);
+        assert_eq!(gs.enemies[0].pos, Pos { x: 10, y: 4 });
+        assert!(gs.bullets.is_empty());
+    }
+
+    #[test]
+    fn scaled_enemy_step_stays_inside_the_walls() {
+        let cfg = GameConfig {
+            scale_enemy_step: true,
+            ..quiet_config()
+        };
+        let mut gs = GameState::new(240, 30, &cfg);
+        assert!(gs.enemy_step(&cfg) > 1);
+        for _ in 0..400 {
+            gs.bounce_formation(&cfg);
+            for e in &gs.enemies {
+                assert!((2..=gs.width - 3).contains(&e.slot().x), "{:?}", e.slot());
+            }
+        }
+        assert!(gs.wave_bounces >= 2);
+    }
+
+    #[test]
+    fn targeted_fire_favors_the_players_column() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        gs.ships[0].pos.x = 20;
+        place(&mut gs, &[(5, 3), (12, 3), (20, 3), (28, 3), (35, 3)]);
+        let mut aligned = 0;
+        for _ in 0..1000 {
+            if gs.choose_shooter(FireStrategy::Targeted).unwrap().x == 20 {
+                aligned += 1;
+            }
+        }
+        // Weighted 8 against 1 for each of the four far columns
+        assert!(
+            aligned > 500,
+            "aligned column fired {aligned} of 1000 times"
+        );
+    }
+
+    #[test]
+    fn later_kills_in_a_wave_are_worth_less() {
+        let cfg = GameConfig {
+            point_decay_per_wave_kill: 2,
+            point_decay_every_ticks: 10,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(10, 5), (20, 5)]);
+        fly_shot(&mut gs, &cfg, 10, 6, 1);
+        let early = gs.score;
+        // One kill in and 30 ticks into the wave
+        gs.tick_count += 30;
+        fly_shot(&mut gs, &cfg, 20, 6, 1);
+        let late = gs.score - early;
+        let kind = EnemyKind::for_row(0).points();
+        assert_eq!(early, cfg.kill_points * kind);
+        assert_eq!(late, (cfg.kill_points - 2 - 3) * kind);
+    }
+
+    #[test]
+    fn enemy_fire_respects_the_bullet_cap() {
+        let cfg = GameConfig {
+            max_enemy_bullets: 4,
+            enemy_bullet_patterns: vec![BulletPattern::Spread],
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(5, 3), (10, 3), (15, 3), (20, 3)]);
+        for _ in 0..100 {
+            gs.enemy_fire(&cfg);
+            assert!(gs.enemy_bullets.len() <= cfg.max_enemy_bullets);
+        }
+        assert_eq!(gs.enemy_bullets.len(), cfg.max_enemy_bullets);
+    }
+
+    #[test]
+    fn endless_mode_escalates_within_bounds() {
+        let cfg = GameConfig {
+            endless: true,
+            enemy_fire_every_ticks: GameConfig::default().enemy_fire_every_ticks,
+            ..quiet_config()
+        };
+        let mut gs = GameState::new(60, 24, &cfg);
+        for wave in 2..=60 {
+            gs.enemies.clear();
+            gs.tick(&cfg);
+            assert_eq!(gs.level, wave);
+            assert!(!gs.game_over && !gs.victory);
+            assert!(gs.spawn_rows <= 6 && gs.spawn_cols <= 12);
+            assert!(gs.enemy_move_every_ticks >= 1);
+            assert!(gs.fire_interval(&cfg) >= MIN_ENDLESS_FIRE_TICKS);
+            assert!(!gs.enemies.is_empty());
+            for e in &gs.enemies {
+                assert!(e.pos.x >= 1 && e.pos.x < gs.width - 1, "{:?}", e.pos);
+                assert!(e.pos.y < gs.ship_row(), "{:?}", e.pos);
+            }
+        }
+    }
+
+    #[test]
+    fn player_step_moves_and_clamps_at_the_walls() {
+        let cfg = GameConfig {
+            player_step: 3,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        gs.ships[0].pos.x = 20;
+        gs.move_ship_left(0);
+        assert_eq!(gs.ships[0].pos.x, 17);
+        gs.move_ship_right(0);
+        gs.move_ship_right(0);
+        assert_eq!(gs.ships[0].pos.x, 23);
+
+        gs.ships[0].pos.x = 2;
+        gs.move_ship_left(0);
+        assert_eq!(gs.ships[0].pos.x, 1);
+        gs.ships[0].pos.x = gs.width - 3;
+        gs.move_ship_right(0);
+        assert_eq!(gs.ships[0].pos.x, gs.width - 2);
+    }
+
+    #[test]
+    fn resize_scales_positions_and_keeps_them_on_the_board() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(2, 2), (21, 6), (37, 10)]);
+        gs.ships[0].pos.x = 30;
+        fly_shot(&mut gs, &cfg, 12, 10, 0);
+        let before: Vec<Pos> = gs.enemies.iter().map(|e| e.pos).collect();
+
+        // Doubling the board roughly doubles every offset from the walls
+        gs.resize(80, 40);
+        let near = |a: u16, b: f64| (a as f64 - b).abs() <= 1.0;
+        for (e, old) in gs.enemies.iter().zip(&before) {
+            assert!(
+                near(e.pos.x, 2.0 + (old.x - 2) as f64 * 75.0 / 35.0),
+                "{:?}",
+                e.pos
+            );
+            assert!(
+                near(e.pos.y, 1.0 + (old.y - 1) as f64 * 36.0 / 16.0),
+                "{:?}",
+                e.pos
+            );
+        }
+        assert!(near(gs.ships[0].pos.x, 1.0 + 29.0 * 77.0 / 37.0));
+        assert_eq!(gs.ships[0].pos.y, 37);
+        assert!(near(gs.bullets[0].pos.x, 1.0 + 11.0 * 77.0 / 37.0));
+
+        // Shrinking hard keeps everything in bounds and apart
+        gs.resize(12, 10);
+        for e in &gs.enemies {
+            assert!((2..=9).contains(&e.pos.x) && e.pos.y >= 1 && e.pos.y < 10);
+        }
+        let mut cells: Vec<Pos> = gs.enemies.iter().map(|e| e.pos).collect();
+        cells.sort_by_key(|p| (p.x, p.y));
+        cells.dedup();
+        assert_eq!(cells.len(), 3);
+        assert!((1..=10).contains(&gs.ships[0].pos.x));
+        assert!(gs.bullets.iter().all(|b| b.pos.x >= 1 && b.pos.x <= 10));
+    }
+
+    #[test]
+    fn descent_grows_with_bounces_up_to_the_cap() {
+        let cfg = GameConfig {
+            descent_growth_every_bounces: 2,
+            max_descent: 3,
+            ..quiet_config()
+        };
+        let mut gs = GameState::new(40, 40, &cfg);
+        gs.enemies.clear();
+        place(&mut gs, &[(2, 2)]);
+        let mut drops = Vec::new();
+        for _ in 0..7 {
+            // Pinned against the left wall, every step is a bounce
+            gs.enemy_direction = -1;
+            gs.bounce_formation(&cfg);
+            drops.push(gs.descent_drop);
+        }
+        assert_eq!(drops, [1, 1, 2, 2, 3, 3, 3]);
+
+        // A new wave starts over from a single row
+        gs.spawn_enemies(&cfg);
+        assert_eq!(gs.wave_bounces, 0);
+        assert_eq!(gs.descent(&cfg), 1);
+    }
+
+    #[test]
+    fn diagonal_shot_hits_an_enemy_on_its_path() {
+        let cfg = GameConfig {
+            diagonal_shots: true,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        gs.ships[0].pos.x = 20;
+        let row = gs.ship_row();
+        // Up-right: one cell over per row climbed
+        place(&mut gs, &[(24, row - 4), (20, row - 4)]);
+        gs.shoot_diagonal(&cfg, 1);
+        for _ in 0..5 {
+            gs.move_bullets(&cfg);
+            gs.resolve_hits(&cfg);
+        }
+        assert_eq!(gs.kills, 1);
+        assert_eq!(gs.enemies.len(), 1);
+        assert_eq!(gs.enemies[0].pos.x, 20);
+    }
+
+    #[test]
+    fn effect_lists_never_exceed_their_caps() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        for i in 0..500 {
+            gs.log_event(format!("event {i}"));
+            gs.sparks.insert(0, (Pos { x: 5, y: 5 }, 200));
+            assert!(gs.events.len() <= EVENT_LOG_LEN);
+            gs.prune_effects();
+            assert!(gs.sparks.len() <= SPARK_CAP);
+        }
+        // Newest entries are the ones kept, and everything expires with its TTL
+        assert_eq!(gs.events[0].0, "event 499");
+        for _ in 0..200 {
+            gs.prune_effects();
+        }
+        assert!(gs.events.is_empty() && gs.sparks.is_empty());
+
+        gs.victory_anim = 2000;
+        while gs.victory_anim > 0 {
+            gs.tick_fireworks();
+            assert!(gs.fireworks.len() <= FIREWORK_CAP);
+        }
+    }
+
+    #[test]
+    fn wave_holds_until_divers_and_swarm_are_gone() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        let home = Pos { x: 10, y: 3 };
+        gs.enemies.push(Enemy {
+            dive: Some(Dive {
+                home,
+                target_x: 10,
+                t: 2,
+                len: 40,
+            }),
+            ..Enemy::new(home, EnemyKind::for_row(0))
+        });
+        gs.tick(&cfg);
+        assert_eq!(gs.level, 1);
+
+        gs.enemies.clear();
+        gs.swarm.push(Pos { x: 10, y: 3 });
+        gs.tick(&cfg);
+        assert_eq!(gs.level, 1);
+
+        gs.swarm.clear();
+        gs.tick(&cfg);
+        assert_eq!(gs.level, 2);
+    }
+
+    #[test]
+    fn bullet_patterns_set_the_shot_directions() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        let row = gs.ship_row();
+        gs.ships[0].pos.x = 20;
+        let above = Pos { x: 20, y: row - 10 };
+        assert_eq!(gs.volley(BulletPattern::Straight, above), [0.0]);
+        assert_eq!(gs.volley(BulletPattern::Spread, above), [0.0, -0.5, 0.5]);
+        assert_eq!(gs.volley(BulletPattern::Aimed, above), [0.0]);
+
+        // Aimed shots lead to the player, but never steeper than one cell per row
+        let left = Pos { x: 15, y: row - 10 };
+        assert_eq!(gs.volley(BulletPattern::Aimed, left), [0.5]);
+        let far = Pos { x: 35, y: row - 5 };
+        assert_eq!(gs.volley(BulletPattern::Aimed, far), [-1.0]);
+
+        // An aimed shot actually arrives at the player's cell
+        gs.enemy_bullets.push(EnemyBullet::new(left, 0.5));
+        for _ in 0..10 {
+            gs.move_enemy_bullets(&cfg);
+        }
+        assert_eq!(gs.enemy_bullets[0].pos, gs.ships[0].pos);
+    }
+
+    #[test]
+    fn wrapping_formation_reenters_from_the_opposite_wall() {
+        let cfg = GameConfig {
+            wrap_enemies: true,
+            ..quiet_config()
+        };
+        // The formation wraps between columns 2 and 37 on a 40-wide board
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(36, 3), (37, 3)]);
+        gs.enemy_direction = 1;
+        gs.wrap_formation(&cfg);
+        let xs: Vec<u16> = gs.enemies.iter().map(|e| e.pos.x).collect();
+        assert_eq!(xs, [37, 2]);
+        assert!(gs.enemies.iter().all(|e| e.pos.y == 3));
+
+        gs.enemy_direction = -1;
+        gs.wrap_formation(&cfg);
+        gs.wrap_formation(&cfg);
+        let xs: Vec<u16> = gs.enemies.iter().map(|e| e.pos.x).collect();
+        assert_eq!(xs, [35, 36]);
+    }
+
+    #[test]
+    fn opening_volley_is_limited_like_normal_fire() {
+        let cfg = GameConfig {
+            opening_volley: 5,
+            ..quiet_config()
+        };
+        let gs = empty_board(&cfg);
+        let x = gs.ships[0].pos.x;
+        // Four shots at 30 heat each lock the gun; the fifth never leaves
+        assert_eq!(bullet_columns(&gs), [x - 4, x - 2, x, x + 2]);
+        assert!(gs.ships[0].overheated);
+        assert_eq!(gs.shots_fired, 4);
+
+        let cfg = GameConfig {
+            opening_volley: 5,
+            magazine_size: 2,
+            ..quiet_config()
+        };
+        let gs = empty_board(&cfg);
+        assert_eq!(bullet_columns(&gs), [x - 2, x]);
+        assert!(gs.reload_ticks > 0);
+    }
+
+    #[test]
+    fn a_missed_shot_breaks_the_combo_when_configured() {
+        for breaks in [false, true] {
+            let cfg = GameConfig {
+                combo_breaks_on_miss: breaks,
+                ..quiet_config()
+            };
+            let mut gs = empty_board(&cfg);
+            gs.combo = 3;
+            gs.combo_ticks = 20;
+            // One row from the top: the next move carries it off the board unhit
+            fly_shot(&mut gs, &cfg, 10, 2, 2);
+            assert!(gs.bullets.is_empty());
+            assert_eq!(gs.combo, if breaks { 0 } else { 3 });
+        }
+
+        // A bullet that already hit something leaves without breaking the combo
+        let cfg = GameConfig {
+            combo_window_ticks: 30,
+            combo_breaks_on_miss: true,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(10, 2)]);
+        fly_shot(&mut gs, &cfg, 10, 4, 4);
+        assert_eq!(gs.kills, 1);
+        assert!(gs.bullets.is_empty());
+        assert_eq!(gs.combo, 1);
+    }
+
+    #[test]
+    fn colliding_bullets_cancel_each_other() {
+        let cfg = GameConfig {
+            bullets_cancel: true,
+            ..quiet_config()
+        };
+        // One row apart: after both move they have swapped cells, passing through
+        // each other without ever sharing one
+        let mut gs = empty_board(&cfg);
+        gs.bullets.push(player_shot(&cfg, 10, 8));
+        gs.enemy_bullets
+            .push(EnemyBullet::new(Pos { x: 10, y: 7 }, 0.0));
+        gs.move_bullets(&cfg);
+        gs.move_enemy_bullets(&cfg);
+        gs.cancel_bullets();
+        assert!(gs.bullets.is_empty());
+        assert!(gs.enemy_bullets.is_empty());
+        assert_eq!(gs.sparks.len(), 1);
+
+        // Two rows apart they meet in the middle cell
+        let mut gs = empty_board(&cfg);
+        gs.bullets.push(player_shot(&cfg, 10, 8));
+        gs.enemy_bullets
+            .push(EnemyBullet::new(Pos { x: 10, y: 6 }, 0.0));
+        gs.move_bullets(&cfg);
+        gs.move_enemy_bullets(&cfg);
+        gs.cancel_bullets();
+        assert!(gs.bullets.is_empty());
+        assert!(gs.enemy_bullets.is_empty());
+
+        // Bullets in different columns fly on
+        let mut gs = empty_board(&cfg);
+        gs.bullets.push(player_shot(&cfg, 10, 8));
+        gs.enemy_bullets
+            .push(EnemyBullet::new(Pos { x: 12, y: 7 }, 0.0));
+        gs.move_bullets(&cfg);
+        gs.move_enemy_bullets(&cfg);
+        gs.cancel_bullets();
+        assert_eq!(gs.bullets.len(), 1);
+        assert_eq!(gs.enemy_bullets.len(), 1);
+    }
+
+    #[test]
+    fn shields_carry_damage_and_regenerate_between_waves() {
+        for regen in [0, 3] {
+            let cfg = GameConfig {
+                shields: 2,
+                shield_regen: regen,
+                ..quiet_config()
+            };
+            let mut gs = empty_board(&cfg);
+            let intact = gs.shields.len();
+            assert!(intact > 6);
+            // Knock out four cells and crack a fifth
+            gs.shields.drain(..4);
+            gs.shields[0].hp = 1;
+            let damage = |gs: &GameState| {
+                (intact - gs.shields.len()) + gs.shields.iter().filter(|s| s.hp < SHIELD_HP).count()
+            };
+            assert_eq!(damage(&gs), 5);
+
+            gs.spawn_enemies(&cfg);
+            assert_eq!(damage(&gs), 5 - regen);
+            assert!(gs.shields.len() <= intact);
+        }
+    }
+
+    #[test]
+    fn player_shots_are_absorbed_by_shields_unless_configured_to_pass() {
+        for absorb in [true, false] {
+            let cfg = GameConfig {
+                shields_absorb_player_shots: absorb,
+                ..quiet_config()
+            };
+            let mut gs = empty_board(&cfg);
+            let cell = Pos { x: 10, y: 12 };
+            gs.shields = vec![ShieldCell {
+                pos: cell,
+                hp: SHIELD_HP,
+            }];
+            fly_shot(&mut gs, &cfg, 10, 13, 1);
+            gs.erode_shields(&cfg);
+            assert_eq!(gs.bullets.is_empty(), absorb);
+            let hp = if absorb { SHIELD_HP - 1 } else { SHIELD_HP };
+            assert_eq!(gs.shields[0].hp, hp);
+
+            // Enemy fire is stopped either way
+            gs.enemy_bullets.push(EnemyBullet::new(cell, 0.0));
+            gs.erode_shields(&cfg);
+            assert!(gs.enemy_bullets.is_empty());
+            assert_eq!(gs.shields[0].hp, hp - 1);
+        }
+    }
+
+    #[test]
+    fn a_kill_briefly_quickens_the_formation() {
+        let cfg = GameConfig {
+            enemy_panic_ticks: 3,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(10, 4), (30, 4)]);
+        let calm = gs.move_interval();
+        assert_eq!(calm, 6);
+
+        fly_shot(&mut gs, &cfg, 10, 6, 2);
+        assert_eq!(gs.enemies.len(), 1);
+        assert_eq!(gs.move_interval(), 3);
+
+        for _ in 0..2 {
+            gs.tick(&cfg);
+            assert_eq!(gs.move_interval(), 3);
+        }
+        gs.tick(&cfg);
+        assert_eq!(gs.move_interval(), calm);
+    }
+
+    #[test]
+    fn level_table_rows_apply_from_their_level_on() {
+        let path = temp_path("levels.txt");
+        fs::write(&path, "# level fire move\n1 40 6\n\n3 20 4\n5 0 2\n").unwrap();
+        let table = load_level_table(path.to_str().unwrap());
+        fs::remove_file(&path).unwrap();
+        let cfg = GameConfig {
+            level_table: table.unwrap(),
+            ..quiet_config()
+        };
+        let levels: Vec<usize> = (1..=7).map(|l| cfg.level_row(l).unwrap().level).collect();
+        assert_eq!(levels, [1, 1, 3, 3, 5, 5, 5]);
+
+        let mut gs = empty_board(&cfg);
+        gs.level = 4;
+        gs.spawn_enemies(&cfg);
+        assert_eq!(gs.enemy_move_every_ticks, 4);
+        assert_eq!(gs.fire_interval(&cfg), 20);
+    }
+
+    #[test]
+    fn level_table_rejects_unsorted_empty_and_malformed_files() {
+        let path = temp_path("bad-levels.txt");
+        for (text, error) in [
+            ("1 40 6\n3 20 4\n2 30 5\n", "increasing order"),
+            ("1 40 6\n1 20 4\n", "increasing order"),
+            ("# nothing here\n\n", "empty"),
+            ("1 40\n", "invalid level row"),
+            ("0 40 6\n", "invalid level row"),
+            ("1 40 0\n", "invalid level row"),
+        ] {
+            fs::write(&path, text).unwrap();
+            let err = load_level_table(path.to_str().unwrap()).unwrap_err();
+            assert!(err.contains(error), "{text:?}: {err}");
+        }
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn formation_holds_still_after_a_descent() {
+        let cfg = GameConfig {
+            enemy_move_every_ticks: 1,
+            descent_pause_ticks: 3,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(37, 4)]);
+        gs.enemy_direction = 1;
+        gs.tick(&cfg);
+        assert_eq!(gs.enemies[0].pos, Pos { x: 37, y: 5 });
+        for _ in 0..3 {
+            gs.tick(&cfg);
+            assert_eq!(gs.enemies[0].pos, Pos { x: 37, y: 5 });
+        }
+        gs.tick(&cfg);
+        assert_eq!(gs.enemies[0].pos, Pos { x: 36, y: 5 });
+    }
+
+    #[test]
+    fn sparse_waves_are_topped_up_to_the_floor() {
+        let cfg = GameConfig {
+            initial_enemy_rows: 1,
+            initial_enemy_cols: 2,
+            min_wave_enemies: 10,
+            ..quiet_config()
+        };
+        let gs = GameState::new(40, 20, &cfg);
+        assert_eq!(gs.enemies.len(), 10);
+        let mut cells: Vec<Pos> = gs.enemies.iter().map(|e| e.pos).collect();
+        cells.sort_by_key(|p| (p.y, p.x));
+        cells.dedup();
+        assert_eq!(cells.len(), 10);
+        for p in cells {
+            assert!(p.x >= 2 && p.x < gs.width - 2, "{p:?}");
+            assert!(p.y >= 2 && p.y < gs.height / 2, "{p:?}");
+        }
+
+        // A floor beyond the free cells fills what fits and no more: a 12x10 board
+        // has a single formation row of eight cells
+        let cfg = GameConfig {
+            min_wave_enemies: 1000,
+            ..cfg
+        };
+        let gs = GameState::new(12, 10, &cfg);
+        assert_eq!(gs.enemies.len(), 8);
+    }
+
+    #[test]
+    fn boss_waves_are_not_topped_up() {
+        let cfg = GameConfig {
+            boss_every: 2,
+            min_wave_enemies: 10,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        gs.next_wave(&cfg);
+        assert_eq!(gs.level, 2);
+        assert!(gs.boss.is_some());
+        assert!(gs.enemies.is_empty());
+    }
+
+    #[test]
+    fn breach_rules_at_the_players_row() {
+        let player = Pos { x: 10, y: 17 };
+        let at = |dx: i16, dy: i16| Pos {
+            x: (10 + dx) as u16,
+            y: (17 + dy) as u16,
+        };
+        let row_rule = quiet_config();
+        let contact = GameConfig {
+            breach_on_contact: true,
+            ..quiet_config()
+        };
+        // (enemy, breaches by row, breaches on contact)
+        for (enemy, by_row, on_contact) in [
+            (at(0, -1), false, false),
+            (at(-1, -1), false, false),
+            (at(0, 0), true, true),
+            (at(-1, 0), true, false),
+            (at(1, 0), true, false),
+            (at(5, 0), true, false),
+            (at(1, 1), true, true),
+        ] {
+            assert_eq!(row_rule.breaches(enemy, player), by_row, "{enemy:?}");
+            assert_eq!(contact.breaches(enemy, player), on_contact, "{enemy:?}");
+        }
+
+        // In play, an enemy beside the ship only ends the run under the row rule
+        for cfg in [row_rule, contact] {
+            let mut gs = empty_board(&cfg);
+            let ship = gs.ships[0].pos;
+            place(&mut gs, &[(ship.x + 1, ship.y)]);
+            gs.tick(&cfg);
+            assert_eq!(gs.game_over, !cfg.breach_on_contact);
+        }
+    }
+
+    #[test]
+    fn wave_modifiers_follow_the_seed() {
+        let sequence = |seed: u64| {
+            let cfg = GameConfig {
+                seed,
+                wave_modifiers: WaveModifier::ALL.to_vec(),
+                ..quiet_config()
+            };
+            let mut gs = GameState::new(40, 20, &cfg);
+            let mut seen = vec![gs.wave_modifier];
+            for _ in 0..11 {
+                gs.enemies.clear();
+                gs.tick(&cfg);
+                seen.push(gs.wave_modifier);
+            }
+            seen
+        };
+        let run = sequence(7);
+        assert_eq!(run, sequence(7));
+        assert_ne!(run, sequence(8));
+        assert!(run.iter().all(Option::is_some));
+        // Heavy fire is never offered to a formation that doesn't shoot
+        assert!(!run.contains(&Some(WaveModifier::HeavyFire)));
+        assert!(run.windows(2).any(|w| w[0] != w[1]));
+    }
+
+    #[test]
+    fn an_enemy_shot_on_its_breach_tick_does_not_breach() {
+        let cfg = GameConfig {
+            enemy_move_every_ticks: 1,
+            ..quiet_config()
+        };
+        let mut gs = about_to_breach(&cfg);
+        let row = gs.ship_row();
+        gs.bullets.push(player_shot(&cfg, 37, row));
+        gs.tick(&cfg);
+        assert_eq!(gs.kills, 1);
+        assert!(!gs.game_over);
+
+        // Without the shot the same tick ends the run
+        let mut gs = about_to_breach(&cfg);
+        gs.tick(&cfg);
+        assert!(gs.game_over);
+    }
+
+    #[test]
+    fn an_enemy_reaching_the_bottom_row_onto_a_bullet_is_destroyed() {
+        let cfg = GameConfig {
+            enemy_move_every_ticks: 1,
+            ..quiet_config()
+        };
+        // The bullet starts one cell below the enemy's landing cell: it moves up into
+        // that cell first, and the enemy then steps onto it
+        let mut gs = about_to_breach(&cfg);
+        let row = gs.ship_row();
+        gs.bullets.push(player_shot(&cfg, 37, row + 1));
+        gs.tick(&cfg);
+        assert_eq!(gs.kills, 1);
+        assert!(!gs.game_over);
+    }
+
+    #[test]
+    fn an_enemy_landing_on_the_ship_ends_the_run_under_either_rule() {
+        for breach_on_contact in [false, true] {
+            let cfg = GameConfig {
+                enemy_move_every_ticks: 1,
+                breach_on_contact,
+                ..quiet_config()
+            };
+            let mut gs = about_to_breach(&cfg);
+            gs.ships[0].pos.x = 37;
+            gs.tick(&cfg);
+            assert!(gs.game_over);
+            assert_eq!(gs.enemies[0].pos, gs.ships[0].pos);
+        }
+    }
+
+    #[test]
+    fn a_pierce_two_bullet_destroys_two_stacked_enemies() {
+        let cfg = GameConfig {
+            bullets_blocked_by_enemies: true,
+            pierce_hits: 2,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(10, 3), (10, 4), (10, 5)]);
+        fly_shot(&mut gs, &cfg, 10, 7, 4);
+        assert_eq!(gs.kills, 2);
+        assert_eq!(gs.enemies.len(), 1);
+        assert_eq!(gs.enemies[0].pos, Pos { x: 10, y: 3 });
+        assert!(gs.bullets.is_empty());
+
+        // Piercing only counts against the stop-on-hit rule: passing bullets ignore it
+        let cfg = GameConfig {
+            bullets_blocked_by_enemies: false,
+            ..cfg
+        };
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(10, 3), (10, 4), (10, 5)]);
+        fly_shot(&mut gs, &cfg, 10, 7, 4);
+        assert_eq!(gs.kills, 3);
+    }
+
+    #[test]
+    fn sudden_death_sets_in_on_the_wave_after_its_trigger() {
+        let cfg = GameConfig {
+            sudden_death: Some(SuddenDeath::Level(2)),
+            enemy_fire_every_ticks: 40,
+            shields: 2,
+            shield_regen: 100,
+            boss_every: 3,
+            wave_modifiers: WaveModifier::ALL.to_vec(),
+            ..quiet_config()
+        };
+        let mut gs = GameState::new(40, 20, &cfg);
+        gs.next_wave(&cfg);
+        assert_eq!(gs.level, 2);
+        assert!(!gs.sudden_death);
+        assert!(!gs.shields.is_empty());
+        assert_eq!(gs.fire_interval(&cfg), 40);
+
+        // Clearing level 2 sends in the finale: top speed, heavy fire, no bunkers,
+        // modifier or boss
+        gs.next_wave(&cfg);
+        assert_eq!(gs.level, 3);
+        assert!(gs.sudden_death);
+        assert_eq!(gs.move_interval(), 1);
+        assert_eq!(gs.fire_interval(&cfg), MIN_ENDLESS_FIRE_TICKS);
+        assert!(gs.shields.is_empty());
+        assert_eq!(gs.wave_modifier, None);
+        assert!(gs.boss.is_none());
+        assert!(!gs.enemies.is_empty());
+
+        // Surviving it wins with the bonus
+        let score = gs.score;
+        gs.enemies.clear();
+        gs.tick(&cfg);
+        assert!(gs.victory);
+        assert_eq!(gs.score, score + cfg.sudden_death_bonus);
+    }
+
+    #[test]
+    fn accuracy_counts_hitting_shots_not_kills() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(10, 4), (10, 5), (30, 4)]);
+        // One shot through two stacked enemies, one miss
+        fly_shot(&mut gs, &cfg, 10, 7, 3);
+        gs.shots_fired = 2;
+        gs.bullets.clear();
+        assert_eq!(gs.kills, 2);
+        assert_eq!(gs.accuracy(), 50);
+        assert!(gs.summary_line().contains(" kills=2 accuracy=50 "));
+    }
+
+    #[test]
+    fn wave_intro_lands_on_its_own_or_waits_for_the_key() {
+        let landed = |gs: &GameState| {
+            gs.enemies.iter().map(|e| e.pos).collect::<Vec<_>>()
+                == gs.enemy_targets.iter().map(|e| e.pos).collect::<Vec<_>>()
+        };
+
+        // Auto-advance: the wave drops in over the configured ticks, then play goes on
+        let cfg = GameConfig {
+            spawn_anim_ticks: 3,
+            ..quiet_config()
+        };
+        let mut gs = GameState::new(40, 20, &cfg);
+        assert!(!landed(&gs));
+        for _ in 0..3 {
+            gs.tick(&cfg);
+        }
+        assert_eq!(gs.spawn_anim, 0);
+        assert!(landed(&gs));
+        assert!(!gs.awaiting_ready);
+        gs.tick(&cfg);
+        assert_eq!(gs.tick_count, 4);
+
+        // Waiting for the key: the landed wave holds until the shoot key
+        let cfg = GameConfig {
+            wave_intro_wait_for_key: true,
+            ..cfg
+        };
+        let mut gs = GameState::new(40, 20, &cfg);
+        for _ in 0..10 {
+            gs.tick(&cfg);
+        }
+        assert!(landed(&gs));
+        assert!(gs.awaiting_ready);
+        assert_eq!(gs.tick_count, 3);
+        gs.apply_input(ReplayInput::SkipSpawn, &cfg);
+        assert!(!gs.awaiting_ready);
+        gs.tick(&cfg);
+        assert_eq!(gs.tick_count, 4);
+
+        // The key also lands a wave that is still dropping in
+        let mut gs = GameState::new(40, 20, &cfg);
+        gs.apply_input(ReplayInput::SkipSpawn, &cfg);
+        assert_eq!(gs.spawn_anim, 0);
+        assert!(landed(&gs));
+        assert!(!gs.awaiting_ready);
+    }
+
+    #[test]
+    fn the_highest_layer_wins_a_shared_cell() {
+        let mut grid = vec![vec![(' ', Style::default(), Layer::Empty); 3]; 2];
+        let cell = Pos { x: 2, y: 1 };
+        let glyph = |grid: &[Vec<(char, Style, Layer)>]| grid[0][1].0;
+
+        plot(&mut grid, cell, Layer::Bullet, ('|', Style::default()));
+        plot(&mut grid, cell, Layer::Enemy, ('W', Style::default()));
+        plot(&mut grid, cell, Layer::Player, ('A', Style::default()));
+        assert_eq!(glyph(&grid), '|');
+
+        plot(&mut grid, cell, Layer::Effect, ('*', Style::default()));
+        assert_eq!(glyph(&grid), '*');
+
+        // Within a layer the last one drawn wins
+        plot(&mut grid, cell, Layer::Effect, ('+', Style::default()));
+        assert_eq!(glyph(&grid), '+');
+
+        // Border cells and cells off the grid are left alone
+        plot(
+            &mut grid,
+            Pos { x: 0, y: 1 },
+            Layer::Ground,
+            ('=', Style::default()),
+        );
+        plot(
+            &mut grid,
+            Pos { x: 9, y: 9 },
+            Layer::Ground,
+            ('=', Style::default()),
+        );
+        let drawn: Vec<char> = grid.iter().flatten().map(|c| c.0).collect();
+        assert_eq!(drawn, [' ', '+', ' ', ' ', ' ', ' ']);
+    }
+
+    #[test]
+    fn enemy_shots_are_stopped_by_their_own_formation() {
+        for blocks in [false, true] {
+            let cfg = GameConfig {
+                formation_blocks_fire: blocks,
+                fire_strategy: FireStrategy::Leftmost,
+                enemy_bullet_patterns: vec![BulletPattern::Spread],
+                ..quiet_config()
+            };
+            let mut gs = empty_board(&cfg);
+            // The spread's right-hand shot drifts into the enemy ahead and to the right
+            place(&mut gs, &[(10, 3), (11, 5)]);
+            gs.enemy_fire(&cfg);
+            assert_eq!(gs.enemy_bullets.len(), 3);
+            gs.move_enemy_bullets(&cfg);
+            let stopped = !gs
+                .enemy_bullets
+                .iter()
+                .any(|b| b.pos == Pos { x: 11, y: 5 });
+            assert_eq!(stopped, blocks);
+            assert_eq!(gs.enemy_bullets.len(), if blocks { 2 } else { 3 });
+            assert_eq!(gs.enemies.len(), 2);
+        }
+    }
+
+    #[test]
+    fn the_magazine_empties_and_reloads() {
+        let cfg = GameConfig {
+            magazine_size: 3,
+            reload_ticks: 4,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(30, 3)]);
+        for left in (0..3).rev() {
+            gs.shoot(&cfg, 0);
+            assert_eq!(gs.ammo_in_mag, left);
+        }
+        // The last round starts the reload, and the empty gun refuses to fire
+        assert_eq!(gs.reload_ticks, 4);
+        gs.shoot(&cfg, 0);
+        assert_eq!(gs.bullets.len(), 3);
+        assert_eq!(gs.blocked_shot, BLOCKED_SHOT_TICKS);
+
+        for _ in 0..3 {
+            gs.tick(&cfg);
+            assert_eq!(gs.ammo_in_mag, 0);
+        }
+        gs.tick(&cfg);
+        assert_eq!(gs.ammo_in_mag, 3);
+        assert_eq!(gs.reload_ticks, 0);
+
+        // Reloading early tops a part-used magazine back up
+        gs.shoot(&cfg, 0);
+        gs.apply_input(ReplayInput::Reload, &cfg);
+        assert_eq!(gs.ammo_in_mag, 2);
+        assert!(gs.magazine_empty(&cfg));
+        for _ in 0..4 {
+            gs.tick(&cfg);
+        }
+        assert_eq!(gs.ammo_in_mag, 3);
+        assert!(!gs.magazine_empty(&cfg));
+    }
+
+    #[test]
+    fn a_campaign_plays_its_levels_in_order_then_wins() {
+        let dir = temp_path("campaign");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("01-opening.txt"), "name Opening\nformation\n# #\n").unwrap();
+        fs::write(
+            dir.join("02-wall.txt"),
+            "name Wall\nmove-every 3\nfire-every 0\nformation\n###\n ###\n",
+        )
+        .unwrap();
+        // Hidden files, like editor swap files, are not levels
+        fs::write(dir.join(".01-opening.txt.swp"), "junk").unwrap();
+        let campaign = load_campaign(dir.to_str().unwrap());
+
+        // A manifest naming a missing file fails with that file's name
+        let manifest = dir.join("campaign.txt");
+        fs::write(&manifest, "01-opening.txt\nmissing.txt\n").unwrap();
+        let err = load_campaign(manifest.to_str().unwrap()).unwrap_err();
+        fs::remove_dir_all(&dir).unwrap();
+        assert!(err.contains("missing.txt"), "{err}");
+
+        let campaign = campaign.unwrap();
+        let names: Vec<&str> = campaign.iter().map(|l| l.name.as_str()).collect();
+        assert_eq!(names, ["Opening", "Wall"]);
+        assert_eq!(campaign[1].move_every_ticks, Some(3));
+        assert_eq!(campaign[1].fire_every_ticks, Some(0));
+
+        let cfg = GameConfig {
+            campaign,
+            ..quiet_config()
+        };
+        let mut gs = GameState::new(40, 20, &cfg);
+        let cells = |gs: &GameState| {
+            let mut cells: Vec<(u16, u16)> =
+                gs.enemies.iter().map(|e| (e.pos.x, e.pos.y)).collect();
+            cells.sort_unstable();
+            cells
+        };
+        assert_eq!(cells(&gs), [(2, 2), (4, 2)]);
+
+        gs.enemies.clear();
+        gs.tick(&cfg);
+        assert_eq!(gs.level, 2);
+        assert_eq!(cells(&gs), [(2, 2), (3, 2), (3, 3), (4, 2), (4, 3), (5, 3)]);
+        assert_eq!(gs.enemy_move_every_ticks, 3);
+        assert!(!gs.victory);
+
+        gs.enemies.clear();
+        gs.tick(&cfg);
+        assert!(gs.victory);
+        assert_eq!(gs.level, 2);
+    }
+
+    #[test]
+    fn a_ship_out_of_lives_no_longer_fires() {
+        let cfg = GameConfig {
+            coop: true,
+            diagonal_shots: true,
+            ..quiet_config()
+        };
+        let mut gs = empty_board(&cfg);
+        let x = gs.ships[0].pos.x;
+        place(&mut gs, &[(x, 3)]);
+        gs.ships[0].lives = 0;
+        // A double press would otherwise start charging the beam
+        gs.press_shoot(&cfg);
+        gs.press_shoot(&cfg);
+        gs.shoot_diagonal(&cfg, 1);
+        gs.fire_beam(&cfg);
+        assert!(gs.bullets.is_empty());
+        assert!(gs.beam_charge.is_none() && gs.beam.is_none());
+        assert_eq!(gs.enemies.len(), 1);
+        assert_eq!(gs.shots_fired, 0);
+
+        // The second player plays on
+        gs.shoot(&cfg, 1);
+        assert_eq!(gs.bullets.len(), 1);
+        assert_eq!(gs.bullets[0].owner, 1);
+    }
+
+    #[test]
+    fn play_time_stops_when_the_run_ends() {
+        let cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        place(&mut gs, &[(10, 3)]);
+        gs.tick(&cfg);
+        assert_eq!(gs.elapsed_ms, cfg.tick_ms);
+        // Neither the game-over animation nor the screen after it adds play time
+        gs.trigger_game_over(&cfg);
+        for _ in 0..cfg.game_over_anim_ticks + 20 {
+            gs.tick(&cfg);
+        }
+        assert_eq!(gs.elapsed_ms, cfg.tick_ms);
+        assert!(
+            gs.summary_line()
+                .contains(&format!(" duration_ms={} ", cfg.tick_ms))
+        );
+    }
+
+    #[test]
+    fn random_enemy_colors_leave_the_game_rng_alone() {
+        let plain = quiet_config();
+        let colored = GameConfig {
+            randomize_enemy_colors: true,
+            ..quiet_config()
+        };
+        let mut a = GameState::new(40, 20, &plain);
+        let mut b = GameState::new(40, 20, &colored);
+        for _ in 0..3 {
+            a.enemies.clear();
+            a.tick(&plain);
+            b.enemies.clear();
+            b.tick(&colored);
+            assert!(ENEMY_PALETTE.contains(&b.enemy_color));
+        }
+        assert_eq!(a.level, b.level);
+        assert_eq!(a.rng.gen_range(0..u64::MAX), b.rng.gen_range(0..u64::MAX));
+    }
+
+    #[test]
+    fn themes_tint_the_frame_and_mono_clears_it() {
+        let area = Rect::new(0, 0, 3, 1);
+        let mut frame = Buffer::empty(area);
+        frame.get_mut(0, 0).set_fg(Color::LightCyan);
+        frame
+            .get_mut(1, 0)
+            .set_fg(Color::Red)
+            .set_bg(Color::LightYellow);
+        frame.get_mut(2, 0).set_fg(Color::DarkGray);
+        let colors = |buf: &Buffer| -> Vec<(Color, Color)> {
+            buf.content.iter().map(|c| (c.fg, c.bg)).collect()
+        };
+
+        let mut amber = frame.clone();
+        Recolor {
+            theme: Theme::Amber,
+            mono: false,
+        }
+        .render(area, &mut amber);
+        assert_eq!(
+            colors(&amber),
+            [
+                (Color::LightYellow, Color::Reset),
+                (Color::Yellow, Color::LightYellow),
+                (Color::DarkGray, Color::Reset),
+            ]
+        );
+
+        // Monochrome wins over the theme
+        let mut mono = frame.clone();
+        Recolor {
+            theme: Theme::Green,
+            mono: true,
+        }
+        .render(area, &mut mono);
+        assert!(
+            colors(&mono)
+                .iter()
+                .all(|&c| c == (Color::Reset, Color::Reset))
+        );
+
+        // The theme row steps through every theme and back
+        let mut cfg = quiet_config();
+        for theme in [Theme::Amber, Theme::Green, Theme::Classic] {
+            Setting::Theme.advance(&mut cfg);
+            assert_eq!(cfg.theme, theme);
+        }
+    }
+
+    #[test]
+    fn aim_assist_follows_the_shot_pattern_and_what_blocks_it() {
+        let mut cfg = quiet_config();
+        let mut gs = empty_board(&cfg);
+        gs.ships[0].pos.x = 20;
+        gs.weapon_level = 2;
+        // Level 2 fires either side of the ship, not through the centre
+        assert_eq!(gs.assist_paths(&cfg), vec![(19, 1), (21, 1)]);
+
+        gs.shields.push(ShieldCell {
+            pos: Pos { x: 19, y: 10 },
+            hp: 1,
+        });
+        assert_eq!(gs.assist_paths(&cfg), vec![(19, 11), (21, 1)]);
+        cfg.shields_absorb_player_shots = false;
+        assert_eq!(gs.assist_paths(&cfg), vec![(19, 1), (21, 1)]);
+        cfg.shields_absorb_player_shots = true;
+
+        gs.boss = Some(Boss {
+            pos: Pos { x: 18, y: 2 },
+            hp: 10,
+            max_hp: 10,
+            direction: 1,
+        });
+        assert_eq!(gs.assist_paths(&cfg), vec![(19, 11), (21, 4)]);
+        place(&mut gs, &[(21, 6)]);
+        assert_eq!(gs.assist_paths(&cfg), vec![(19, 11), (21, 7)]);
+    }
 }